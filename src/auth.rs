@@ -2,12 +2,12 @@
 //!
 //! These API endpoints are used to manage users and roles.
 
-use http::{StatusCode, Uri};
+use http::{header::HeaderValue, Method, StatusCode, Uri};
 use serde::de::DeserializeOwned;
 use serde_derive::{Deserialize, Serialize};
 use serde_json;
 
-use crate::client::{parse_empty_response, Client, ClusterInfo, Response};
+use crate::client::{parse_empty_response, response_cluster_info, Client, HttpResponse, Response};
 use crate::error::Error;
 
 /// The structure returned by the `GET /v2/auth/enable` endpoint.
@@ -72,6 +72,7 @@ impl UserDetail {
 
 /// A list of all users.
 #[derive(Debug, Clone, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[allow(dead_code)]
 struct Users {
     users: Option<Vec<UserDetail>>,
 }
@@ -251,6 +252,7 @@ impl Role {
 
 /// A list of all roles.
 #[derive(Debug, Clone, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[allow(dead_code)]
 struct Roles {
     roles: Option<Vec<Role>>,
 }
@@ -412,6 +414,10 @@ impl Permission {
 type EtcdAuthResult<T> = Result<Response<T>, Vec<Error>>;
 
 /// Creates a new role.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(name = "etcd.auth.create_role", skip_all, fields(role = role.name.as_str()))
+)]
 pub async fn create_role(client: &Client, role: Role) -> EtcdAuthResult<Role> {
     let body = serde_json::to_string(&role).map_err(|e| vec![e.into()])?;
 
@@ -421,12 +427,11 @@ pub async fn create_role(client: &Client, role: Role) -> EtcdAuthResult<Role> {
             let url = build_url(endpoint, &format!("/roles/{}", role.name));
             async move {
                 let response = client
-                    .http_client()
-                    .put(url)
+                    .http_request(Method::PUT, url)
                     .body(body)
                     .header(
                         http::header::CONTENT_TYPE,
-                        "application/x-www-form-urlencoded",
+                        HeaderValue::from_static("application/x-www-form-urlencoded"),
                     )
                     .send()
                     .await?;
@@ -440,6 +445,10 @@ pub async fn create_role(client: &Client, role: Role) -> EtcdAuthResult<Role> {
 }
 
 /// Creates a new user.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(name = "etcd.auth.create_user", skip_all, fields(user = user.name.as_str()))
+)]
 pub async fn create_user(client: &Client, user: NewUser) -> EtcdAuthResult<User> {
     let body = serde_json::to_string(&user).map_err(|e| vec![e.into()])?;
 
@@ -449,12 +458,11 @@ pub async fn create_user(client: &Client, user: NewUser) -> EtcdAuthResult<User>
             let body = body.clone();
             async move {
                 let response = client
-                    .http_client()
-                    .put(url)
+                    .http_request(Method::PUT, url)
                     .body(body)
                     .header(
                         http::header::CONTENT_TYPE,
-                        "application/x-www-form-urlencoded",
+                        HeaderValue::from_static("application/x-www-form-urlencoded"),
                     )
                     .send()
                     .await?;
@@ -468,6 +476,10 @@ pub async fn create_user(client: &Client, user: NewUser) -> EtcdAuthResult<User>
 }
 
 /// Deletes a role.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(name = "etcd.auth.delete_role", skip_all, fields(role = role_name.as_ref()))
+)]
 pub async fn delete_role<N>(client: &Client, role_name: N) -> EtcdAuthResult<()>
 where
     N: AsRef<str>,
@@ -478,7 +490,7 @@ where
         .first_ok(|client, endpoint| {
             let url = build_url(endpoint, &format!("/roles/{}", role_name));
             async move {
-                let response = client.http_client().delete(url).send().await?;
+                let response = client.http_request(Method::DELETE, url).send().await?;
                 parse_empty_response(response).await
             }
         })
@@ -486,6 +498,10 @@ where
 }
 
 /// Deletes a user.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(name = "etcd.auth.delete_user", skip_all, fields(user = user_name.as_ref()))
+)]
 pub async fn delete_user<N>(client: &Client, user_name: N) -> EtcdAuthResult<()>
 where
     N: AsRef<str>,
@@ -495,7 +511,7 @@ where
         .first_ok(|client, endpoint| {
             let url = build_url(endpoint, &format!("/users/{}", user_name));
             async move {
-                let response = client.http_client().delete(url).send().await?;
+                let response = client.http_request(Method::DELETE, url).send().await?;
                 parse_empty_response(response).await
             }
         })
@@ -503,12 +519,16 @@ where
 }
 
 /// Attempts to disable the auth system.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(name = "etcd.auth.disable", skip_all)
+)]
 pub async fn disable(client: &Client) -> EtcdAuthResult<AuthChange> {
     client
         .first_ok(|client, endpoint| {
             let url = build_url(endpoint, "/enable");
             async move {
-                let response = client.http_client().delete(url).send().await?;
+                let response = client.http_request(Method::DELETE, url).send().await?;
                 parse_auth_change_response(response)
             }
         })
@@ -516,12 +536,16 @@ pub async fn disable(client: &Client) -> EtcdAuthResult<AuthChange> {
 }
 
 /// Attempts to enable the auth system.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(name = "etcd.auth.enable", skip_all)
+)]
 pub async fn enable(client: &Client) -> EtcdAuthResult<AuthChange> {
     client
         .first_ok(|client, endpoint| {
             let url = build_url(endpoint, "/enable");
             async move {
-                let response = client.http_client().put(url).send().await?;
+                let response = client.http_request(Method::PUT, url).send().await?;
                 parse_auth_change_response(response)
             }
         })
@@ -529,6 +553,10 @@ pub async fn enable(client: &Client) -> EtcdAuthResult<AuthChange> {
 }
 
 /// Get a role.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(name = "etcd.auth.get_role", skip_all, fields(role = role_name.as_ref()))
+)]
 pub async fn get_role<N>(client: &Client, role_name: N) -> EtcdAuthResult<Role>
 where
     N: AsRef<str>,
@@ -539,7 +567,7 @@ where
         .first_ok(|client, endpoint| {
             let url = build_url(endpoint, &format!("/roles/{}", role_name));
             async move {
-                let response = client.http_client().get(url).send().await?;
+                let response = client.http_request(Method::GET, url).send().await?;
                 parse_auth_response(response, |s| s == StatusCode::OK).await
             }
         })
@@ -547,12 +575,16 @@ where
 }
 
 /// Get a role.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(name = "etcd.auth.get_roles", skip_all)
+)]
 pub async fn get_roles<N>(client: &Client) -> EtcdAuthResult<Vec<Role>> {
     client
         .first_ok(|client, endpoint| {
             let url = build_url(endpoint, "/roles");
             async move {
-                let response = client.http_client().get(url).send().await?;
+                let response = client.http_request(Method::GET, url).send().await?;
                 parse_auth_response(response, |s| s == StatusCode::OK).await
             }
         })
@@ -560,6 +592,10 @@ pub async fn get_roles<N>(client: &Client) -> EtcdAuthResult<Vec<Role>> {
 }
 
 /// Get a user.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(name = "etcd.auth.get_user", skip_all, fields(user = user_name.as_ref()))
+)]
 pub async fn get_user<N>(client: &Client, user_name: N) -> EtcdAuthResult<User>
 where
     N: AsRef<str>,
@@ -570,7 +606,7 @@ where
         .first_ok(|client, endpoint| {
             let url = build_url(endpoint, &format!("/users/{}", user_name));
             async move {
-                let response = client.http_client().get(url).send().await?;
+                let response = client.http_request(Method::GET, url).send().await?;
                 parse_auth_response(response, |s| s == StatusCode::OK).await
             }
         })
@@ -578,12 +614,16 @@ where
 }
 
 /// Gets all users.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(name = "etcd.auth.get_users", skip_all)
+)]
 pub async fn get_users<N>(client: &Client) -> EtcdAuthResult<Vec<User>> {
     client
         .first_ok(|client, endpoint| {
             let url = build_url(endpoint, "/users");
             async move {
-                let response = client.http_client().get(url).send().await?;
+                let response = client.http_request(Method::GET, url).send().await?;
                 parse_auth_response(response, |s| s == StatusCode::OK).await
             }
         })
@@ -591,12 +631,16 @@ pub async fn get_users<N>(client: &Client) -> EtcdAuthResult<Vec<User>> {
 }
 
 /// Determines whether or not the auth system is enabled.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(name = "etcd.auth.status", skip_all)
+)]
 pub async fn status(client: &Client) -> EtcdAuthResult<bool> {
     client
         .first_ok(|client, endpoint| {
             let url = build_url(endpoint, "/enable");
             async move {
-                let response = client.http_client().get(url).send().await?;
+                let response = client.http_request(Method::GET, url).send().await?;
                 let response: Response<AuthStatus> =
                     parse_auth_response(response, |s| s == StatusCode::OK).await?;
 
@@ -610,6 +654,10 @@ pub async fn status(client: &Client) -> EtcdAuthResult<bool> {
 }
 
 /// Updates an existing role.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(name = "etcd.auth.update_role", skip_all, fields(role = role.name.as_str()))
+)]
 pub async fn update_role(client: &Client, role: RoleUpdate) -> EtcdAuthResult<Role> {
     let body = serde_json::to_string(&role).map_err(|e| vec![e.into()])?;
 
@@ -619,12 +667,11 @@ pub async fn update_role(client: &Client, role: RoleUpdate) -> EtcdAuthResult<Ro
             let body = body.clone();
             async move {
                 let response = client
-                    .http_client()
-                    .put(url)
+                    .http_request(Method::PUT, url)
                     .body(body)
                     .header(
                         http::header::CONTENT_TYPE,
-                        "application/x-www-form-urlencoded",
+                        HeaderValue::from_static("application/x-www-form-urlencoded"),
                     )
                     .send()
                     .await?;
@@ -635,6 +682,10 @@ pub async fn update_role(client: &Client, role: RoleUpdate) -> EtcdAuthResult<Ro
 }
 
 /// Updates an existing user
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(name = "etcd.auth.update_user", skip_all, fields(user = user.name.as_str()))
+)]
 pub async fn update_user(client: &Client, user: UserUpdate) -> EtcdAuthResult<User> {
     let body = serde_json::to_string(&user).map_err(|e| vec![e.into()])?;
 
@@ -644,12 +695,11 @@ pub async fn update_user(client: &Client, user: UserUpdate) -> EtcdAuthResult<Us
             let body = body.clone();
             async move {
                 let response = client
-                    .http_client()
-                    .put(url)
+                    .http_request(Method::PUT, url)
                     .body(body)
                     .header(
                         http::header::CONTENT_TYPE,
-                        "application/x-www-form-urlencoded",
+                        HeaderValue::from_static("application/x-www-form-urlencoded"),
                     )
                     .send()
                     .await?;
@@ -665,15 +715,15 @@ fn build_url(endpoint: &Uri, path: &str) -> String {
 }
 
 async fn parse_auth_response<T>(
-    response: reqwest::Response,
+    response: HttpResponse,
     status_code_is_success: impl FnOnce(StatusCode) -> bool,
 ) -> Result<Response<T>, Error>
 where
     T: DeserializeOwned,
 {
-    let status_code = response.status();
-    let cluster_info = ClusterInfo::from(response.headers());
-    let body = response.bytes().await?;
+    let status_code = response.status;
+    let cluster_info = response_cluster_info(&response);
+    let body = response.body.bytes().await?;
     if status_code_is_success(status_code) {
         match serde_json::from_slice::<T>(&body) {
             Ok(data) => Ok(Response { data, cluster_info }),
@@ -684,9 +734,9 @@ where
     }
 }
 
-fn parse_auth_change_response(response: reqwest::Response) -> Result<Response<AuthChange>, Error> {
-    let status = response.status();
-    let cluster_info = ClusterInfo::from(response.headers());
+fn parse_auth_change_response(response: HttpResponse) -> Result<Response<AuthChange>, Error> {
+    let status = response.status;
+    let cluster_info = response_cluster_info(&response);
     match status {
         StatusCode::OK => Ok(Response {
             data: AuthChange::Changed,
@@ -1,23 +1,63 @@
 //! Contains the etcd client. All API calls are made via the client.
 
-use std::{future::Future, sync::Arc, time::Duration};
+#[cfg(any(feature = "tls", feature = "rustls-tls"))]
+use std::path::Path;
+use std::{
+    future::Future,
+    sync::{Arc, PoisonError, RwLock, Weak},
+    time::{Duration, Instant},
+};
+
+use bytes::Bytes;
+use futures::{
+    future::{self, Either},
+    stream::{FuturesUnordered, StreamExt},
+};
 
 use http::{
-    header::{HeaderMap, HeaderValue},
-    StatusCode, Uri,
+    header::{Entry, HeaderMap, HeaderName, HeaderValue},
+    Method, StatusCode, Uri,
 };
 use log::error;
-use rand::{prelude::SliceRandom, thread_rng};
-use reqwest::{Certificate, Identity, IntoUrl};
+#[cfg(any(feature = "tls", feature = "rustls-tls"))]
+use reqwest::{Certificate, Identity};
 use serde::de::DeserializeOwned;
 use serde_derive::{Deserialize, Serialize};
-use serde_json;
+use url::Url;
 
 use crate::{
+    dns::{self, DnsResolver, SrvResolver},
     error::{ApiError, Error},
-    VersionInfo,
+    members, VersionInfo,
 };
 
+pub use self::health::EndpointHealth;
+pub use self::interceptor::{InterceptedRequest, Interceptor, RequestOutcome};
+pub use self::metrics::{MetricsSink, RequestMetrics};
+pub use self::request::{CancellationToken, RequestOptions};
+pub(crate) use self::retry::jittered_backoff;
+pub use self::retry::RetryPolicy;
+pub use self::transport::{HttpRequest, HttpResponse, ResponseBody, Transport};
+
+use self::health::{EjectionPolicy, Endpoint};
+use self::leader::LeaderCache;
+use self::metrics::{Metrics, OpenWatch};
+#[cfg(any(feature = "tls", feature = "rustls-tls"))]
+use self::tls::{TlsFiles, TlsMaterial};
+use self::transport::ReqwestTransport;
+
+mod health;
+mod interceptor;
+mod leader;
+mod metrics;
+mod request;
+mod retry;
+#[cfg(any(feature = "tls", feature = "rustls-tls"))]
+mod tls;
+mod trace;
+mod transport;
+mod unix;
+
 const XETCD_CLUSTER_ID: &str = "X-Etcd-Cluster-Id";
 const XETCD_INDEX: &str = "X-Etcd-Index";
 const XRAFT_INDEX: &str = "X-Raft-Index";
@@ -28,8 +68,16 @@ const XRAFT_TERM: &str = "X-Raft-Term";
 /// All API calls require a client.
 #[derive(Clone, Debug)]
 pub struct Client {
-    endpoints: Arc<Vec<Uri>>,
-    http_client: reqwest::Client,
+    endpoints: Arc<RwLock<Vec<Arc<Endpoint>>>>,
+    ejection_policy: EjectionPolicy,
+    retry_policy: Option<RetryPolicy>,
+    leader: Option<Arc<LeaderCache>>,
+    request_options: RequestOptions,
+    interceptors: Vec<Arc<dyn Interceptor>>,
+    metrics: Option<Metrics>,
+    default_headers: HeaderMap,
+    request_timeout: Option<Duration>,
+    transport: Arc<dyn Transport>,
 }
 
 /// A username and password to use for HTTP basic authentication.
@@ -48,6 +96,17 @@ pub struct Health {
     pub health: String,
 }
 
+/// The DNS SRV records a client's endpoints were discovered from.
+#[derive(Clone, Debug)]
+struct DnsSrv {
+    /// The domain the records are published under.
+    domain: String,
+    /// The resolver used to look up the records.
+    resolver: Arc<dyn SrvResolver>,
+    /// How often to look up the records again, if at all.
+    refresh_interval: Option<Duration>,
+}
+
 /// A client builder is used to configure and create a client.
 ///
 /// Use with [`ClientBuilder::new`], however if you don't require advanced configuration,
@@ -59,14 +118,35 @@ pub struct ClientBuilder {
     tcp_keepalive: Option<Duration>,
     request_timeout: Option<Duration>,
     connect_timeout: Duration,
-    #[cfg(feature = "tls")]
+    ejection_policy: EjectionPolicy,
+    retry_policy: Option<RetryPolicy>,
+    auto_sync_interval: Option<Duration>,
+    dns_srv: Option<DnsSrv>,
+    leader_routing: bool,
+    interceptors: Vec<Arc<dyn Interceptor>>,
+    metrics_sink: Option<Arc<dyn MetricsSink>>,
+    transport: Option<Arc<dyn Transport>>,
+    proxy: Option<reqwest::Proxy>,
+    proxy_auth: Option<BasicAuth>,
+    no_proxy: Vec<String>,
+    #[cfg(any(feature = "tls", feature = "rustls-tls"))]
     tls_client_identity: Option<Identity>,
-    #[cfg(feature = "tls")]
+    #[cfg(any(feature = "tls", feature = "rustls-tls"))]
     tls_root_certificates: Vec<Certificate>,
+    #[cfg(feature = "rustls-tls")]
+    tls_use_rustls: bool,
+    #[cfg(any(feature = "tls", feature = "rustls-tls"))]
+    tls_files: Option<TlsFiles>,
+    #[cfg(any(feature = "tls", feature = "rustls-tls"))]
+    tls_reload_interval: Option<Duration>,
 }
 
 impl ClientBuilder {
     /// Creates a new client builder that can be used to configure and customize the etcd client.
+    ///
+    /// On Unix platforms, an endpoint may also be a Unix domain socket that etcd listens on, such as
+    /// `unix:///var/run/etcd.sock`.
+    ///
     /// # Errors
     ///
     /// Panics if no endpoints are provided or if any of the endpoints is an invalid URL.
@@ -76,23 +156,82 @@ impl ClientBuilder {
         }
 
         let endpoints = endpoints
-            .into_iter()
+            .iter()
             .map(|e| {
-                e.parse()
-                    .expect(&format!("invariant: could not parse endpoint: {}", e))
+                unix::parse_endpoint(e)
+                    .unwrap_or_else(|_| panic!("invariant: could not parse endpoint: {}", e))
             })
             .collect();
 
+        Self::from_uris(endpoints)
+    }
+
+    /// Creates a new client builder with endpoints discovered from the DNS SRV records of a domain.
+    ///
+    /// Both `_etcd-client-ssl._tcp.<domain>` and `_etcd-client._tcp.<domain>` records are looked
+    /// up, and used as HTTPS and HTTP endpoints respectively. Lookups are sent to the name servers
+    /// listed in `/etc/resolv.conf`, and a domain without a trailing dot is also looked up relative
+    /// to the search domains listed there.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the records cannot be looked up, or if no records are found.
+    pub async fn from_dns_srv(domain: &str) -> Result<Self, Error> {
+        let resolver = DnsResolver::system().map_err(Error::Dns)?;
+        Self::from_dns_srv_with_resolver(domain, Arc::new(resolver)).await
+    }
+
+    /// Creates a new client builder with endpoints discovered from the DNS SRV records of a
+    /// domain, looked up with a specific resolver.
+    ///
+    /// See [`ClientBuilder::from_dns_srv`] for the records looked up.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the records cannot be looked up, or if no records are found.
+    pub async fn from_dns_srv_with_resolver(
+        domain: &str,
+        resolver: Arc<dyn SrvResolver>,
+    ) -> Result<Self, Error> {
+        let endpoints = dns::resolve_endpoints(&*resolver, domain).await?;
+        let mut builder = Self::from_uris(endpoints);
+        builder.dns_srv = Some(DnsSrv {
+            domain: domain.to_owned(),
+            resolver,
+            refresh_interval: None,
+        });
+        Ok(builder)
+    }
+
+    /// Creates a new client builder for the given endpoints.
+    fn from_uris(endpoints: Vec<Uri>) -> Self {
         Self {
             endpoints,
             basic_auth: None,
             connect_timeout: Duration::from_secs(90),
             tcp_keepalive: None,
             request_timeout: None,
-            #[cfg(feature = "tls")]
+            ejection_policy: EjectionPolicy::default(),
+            retry_policy: None,
+            auto_sync_interval: None,
+            dns_srv: None,
+            leader_routing: false,
+            interceptors: Vec::new(),
+            metrics_sink: None,
+            transport: None,
+            proxy: None,
+            proxy_auth: None,
+            no_proxy: Vec::new(),
+            #[cfg(any(feature = "tls", feature = "rustls-tls"))]
             tls_client_identity: None,
-            #[cfg(feature = "tls")]
+            #[cfg(any(feature = "tls", feature = "rustls-tls"))]
             tls_root_certificates: Vec::new(),
+            #[cfg(feature = "rustls-tls")]
+            tls_use_rustls: false,
+            #[cfg(any(feature = "tls", feature = "rustls-tls"))]
+            tls_files: None,
+            #[cfg(any(feature = "tls", feature = "rustls-tls"))]
+            tls_reload_interval: None,
         }
     }
 
@@ -130,14 +269,166 @@ impl ClientBuilder {
         self
     }
 
-    #[cfg(feature = "tls")]
+    /// Configures when the client stops sending requests to a failing endpoint.
+    ///
+    /// An endpoint that fails `failure_threshold` requests in a row is skipped for `cooldown`,
+    /// after which a health check is sent to it in the background to check whether it has
+    /// recovered. Requests fail
+    /// because of network errors or internal etcd server errors, not because of errors such as a
+    /// missing key.
+    ///
+    /// The default is to skip an endpoint for 30 seconds after 3 failed requests in a row.
+    pub fn with_endpoint_ejection(mut self, failure_threshold: u32, cooldown: Duration) -> Self {
+        self.ejection_policy = EjectionPolicy {
+            failure_threshold,
+            cooldown,
+        };
+        self
+    }
+
+    /// Configures the client to retry requests that failed on every endpoint.
+    ///
+    /// By default, each endpoint is tried once per request.
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = Some(retry_policy);
+        self
+    }
+
+    /// Configures the client to keep its endpoints up to date with the cluster's membership.
+    ///
+    /// Every `interval`, a background task calls [`Client::sync_endpoints`] to replace the client's
+    /// endpoints with the client URLs of the current cluster members. The endpoints given to
+    /// [`ClientBuilder::new`] are only used until the first sync. The task stops once the client
+    /// and all of its clones have been dropped.
+    ///
+    /// # Panics
+    ///
+    /// [`ClientBuilder::build`] panics if it is not called from within a Tokio runtime.
+    pub fn with_auto_sync(mut self, interval: Duration) -> Self {
+        self.auto_sync_interval = Some(interval);
+        self
+    }
+
+    /// Configures the client to look up the DNS SRV records its endpoints were discovered from
+    /// again every `interval`, replacing its endpoints with the records found.
+    ///
+    /// Has no effect unless the builder was created with [`ClientBuilder::from_dns_srv`] or
+    /// [`ClientBuilder::from_dns_srv_with_resolver`]. The lookups stop once the client and all of
+    /// its clones have been dropped.
+    ///
+    /// # Panics
+    ///
+    /// [`ClientBuilder::build`] panics if it is not called from within a Tokio runtime.
+    pub fn with_dns_srv_refresh(mut self, interval: Duration) -> Self {
+        if let Some(ref mut dns_srv) = self.dns_srv {
+            dns_srv.refresh_interval = Some(interval);
+        }
+        self
+    }
+
+    /// Configures the client to send writes to the cluster leader first.
+    ///
+    /// Followers forward writes to the leader, so sending them to the leader directly saves a
+    /// network hop. The leader is found through the statistics and members APIs the first time a
+    /// key is set or deleted, and is looked up again once a response shows a new raft term or the
+    /// leader stops responding. Other requests are sent to endpoints as usual.
+    ///
+    /// The leader's endpoint is recognized by comparing the client URLs it advertises with the
+    /// client's endpoints, so it must advertise one of them exactly. If it doesn't, for example
+    /// because it advertises `0.0.0.0`, a warning is logged and writes are sent to endpoints as
+    /// usual.
+    pub fn with_leader_routing(mut self) -> Self {
+        self.leader_routing = true;
+        self
+    }
+
+    /// Adds an interceptor that is called before and after every HTTP request the client makes.
+    ///
+    /// NOTE: Calling this function multiple times adds each interceptor. They are called in the
+    /// order they were added.
+    pub fn with_interceptor(mut self, interceptor: Arc<dyn Interceptor>) -> Self {
+        self.interceptors.push(interceptor);
+        self
+    }
+
+    /// Configures the client to record metrics about the requests it makes to `sink`.
+    pub fn with_metrics_sink(mut self, sink: Arc<dyn MetricsSink>) -> Self {
+        self.metrics_sink = Some(sink);
+        self
+    }
+
+    /// Configures the client to send its HTTP requests with `transport` rather than `reqwest`.
+    ///
+    /// The transport replaces the client's TLS, proxy, connection and `unix://` endpoint support,
+    /// so the settings for those are ignored. Basic auth, request timeouts, interceptors, metrics,
+    /// retries and failover still apply.
+    pub fn with_transport(mut self, transport: Arc<dyn Transport>) -> Self {
+        self.transport = Some(transport);
+        self
+    }
+
+    /// Configures the client to connect to etcd through a proxy.
+    ///
+    /// HTTP and HTTPS proxies are supported, and SOCKS5 proxies are supported with the `socks`
+    /// feature. HTTPS endpoints are reached by tunneling through the proxy, so that TLS is still
+    /// negotiated with etcd itself. Watches are sent through the proxy like other requests, so the
+    /// proxy must allow requests to stay open for as long as a watch may wait for a change.
+    ///
+    /// Without a proxy, the client uses the proxies configured by the `HTTP_PROXY`, `HTTPS_PROXY`
+    /// and `NO_PROXY` environment variables, if any.
+    ///
+    /// # Parameters
+    ///
+    /// * url: The proxy's URL, such as `http://proxy.example.com:3128` or
+    ///   `socks5://proxy.example.com:1080`.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the URL cannot be parsed, or if its scheme is not supported.
+    pub fn with_proxy(mut self, url: &str) -> Result<Self, Error> {
+        self.proxy = Some(reqwest::Proxy::all(url)?);
+        Ok(self)
+    }
+
+    /// Configures the client to authenticate to the proxy given to [`ClientBuilder::with_proxy`]
+    /// with basic auth, with the given username and password.
+    pub fn with_proxy_auth(
+        mut self,
+        username: impl Into<String>,
+        password: impl Into<String>,
+    ) -> Self {
+        self.proxy_auth = Some(BasicAuth {
+            username: username.into(),
+            password: password.into(),
+        });
+        self
+    }
+
+    /// Configures the client to connect to the given hosts directly rather than through the proxy
+    /// given to [`ClientBuilder::with_proxy`].
+    ///
+    /// Each host may be a domain name, which also matches its subdomains, an IP address, or an IP
+    /// network such as `10.0.0.0/8`.
+    ///
+    /// NOTE: Calling this function multiple times adds each list of hosts.
+    pub fn with_no_proxy(mut self, hosts: &[&str]) -> Self {
+        self.no_proxy
+            .extend(hosts.iter().map(|host| (*host).to_owned()));
+        self
+    }
+
+    #[cfg(any(feature = "tls", feature = "rustls-tls"))]
     /// Uses a specific client certificate ([`Identity`]) for TLS connections to etcd.
+    ///
+    /// The identity must be created for the TLS backend in use: with `Identity::from_pkcs12_der`
+    /// or `Identity::from_pkcs8_pem` for the `tls` feature, or with `Identity::from_pem` for the
+    /// `rustls-tls` feature.
     pub fn with_client_identity(mut self, identity: Identity) -> Self {
         self.tls_client_identity = Some(identity);
         self
     }
 
-    #[cfg(feature = "tls")]
+    #[cfg(any(feature = "tls", feature = "rustls-tls"))]
     /// Adds a specific root certificate that will be accepted by the client.
     ///
     /// Useful if your etcd server is using TLS with self-signed certificates.
@@ -149,55 +440,271 @@ impl ClientBuilder {
         self
     }
 
+    #[cfg(feature = "rustls-tls")]
+    /// Uses rustls for TLS connections to etcd.
+    ///
+    /// Only needed if both the `tls` and `rustls-tls` features are enabled, in which case native
+    /// TLS is used by default. If only `rustls-tls` is enabled, rustls is always used.
+    pub fn with_rustls_tls(mut self) -> Self {
+        self.tls_use_rustls = true;
+        self
+    }
+
+    #[cfg(any(feature = "tls", feature = "rustls-tls"))]
+    /// Loads the root certificates, client certificate and private key for TLS connections to etcd
+    /// from PEM files.
+    ///
+    /// The root certificates are accepted in addition to any added with
+    /// [`ClientBuilder::with_root_certificate`], and the client certificate replaces any given to
+    /// [`ClientBuilder::with_client_identity`]. With the `tls` feature, the private key must be in
+    /// PKCS #8 format.
+    ///
+    /// The files are loaded by [`ClientBuilder::try_build`], once the TLS backend is known, so
+    /// this function may be called before or after [`ClientBuilder::with_rustls_tls`].
+    ///
+    /// # Parameters
+    ///
+    /// * ca: The path to a PEM file with one or more root certificates.
+    /// * cert: The path to a PEM file with the client certificate.
+    /// * key: The path to a PEM file with the client certificate's private key.
+    pub fn with_tls_files(
+        mut self,
+        ca: impl AsRef<Path>,
+        cert: impl AsRef<Path>,
+        key: impl AsRef<Path>,
+    ) -> Self {
+        self.tls_files = Some(TlsFiles::new(
+            ca.as_ref().to_path_buf(),
+            cert.as_ref().to_path_buf(),
+            key.as_ref().to_path_buf(),
+        ));
+        self
+    }
+
+    #[cfg(any(feature = "tls", feature = "rustls-tls"))]
+    /// Configures the client to check the files given to [`ClientBuilder::with_tls_files`] for
+    /// changes every `interval`, and to load them again when they change.
+    ///
+    /// Once the files have been loaded, the client and all of its clones make new requests with an
+    /// HTTP client that uses them. Requests already in progress, including watches, carry on with
+    /// the certificates they started with. If the files cannot be loaded, for example because
+    /// they are in the middle of being replaced, the previous certificates are kept and the files
+    /// are loaded again after the next `interval`. The checks stop once the client and all of its
+    /// clones have been dropped.
+    ///
+    /// Has no effect unless [`ClientBuilder::with_tls_files`] is also called.
+    ///
+    /// # Panics
+    ///
+    /// [`ClientBuilder::build`] panics if it is not called from within a Tokio runtime.
+    pub fn with_tls_reload(mut self, interval: Duration) -> Self {
+        self.tls_reload_interval = Some(interval);
+        self
+    }
+
+    #[cfg(any(feature = "tls", feature = "rustls-tls"))]
+    /// Returns whether TLS connections will use rustls rather than native TLS.
+    fn uses_rustls(&self) -> bool {
+        #[cfg(feature = "rustls-tls")]
+        {
+            !cfg!(feature = "tls") || self.tls_use_rustls
+        }
+
+        #[cfg(not(feature = "rustls-tls"))]
+        {
+            false
+        }
+    }
+
     /// Constructs a client from the builder.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the HTTP client cannot be created, such as when the files given to
+    /// [`ClientBuilder::with_tls_files`] cannot be loaded. Use [`ClientBuilder::try_build`] to
+    /// handle these failures.
     pub fn build(self) -> Client {
-        let client_builder = reqwest::ClientBuilder::new();
-        let client_builder = client_builder.connect_timeout(self.connect_timeout);
-        let client_builder = match self.basic_auth {
-            Some(auth) => {
-                let mut headers = HeaderMap::new();
-                let basic_auth = base64::encode(format!("{}:{}", auth.username, auth.password));
-                headers.insert(
-                    reqwest::header::AUTHORIZATION,
-                    HeaderValue::from_str(&format!("Basic {}", basic_auth))
-                        .expect("invariant: could not create basic auth header."),
-                );
-                client_builder.default_headers(headers)
+        self.try_build()
+            .expect("invariant: could not create http client")
+    }
+
+    /// Constructs a client from the builder, loading any files given to
+    /// [`ClientBuilder::with_tls_files`].
+    ///
+    /// # Errors
+    ///
+    /// Fails if any of the TLS files cannot be read or parsed, or if the HTTP client cannot be
+    /// created with the configured TLS settings.
+    pub fn try_build(self) -> Result<Client, Error> {
+        let mut default_headers = HeaderMap::new();
+        if let Some(ref auth) = self.basic_auth {
+            let basic_auth = base64::encode(format!("{}:{}", auth.username, auth.password));
+            default_headers.insert(
+                http::header::AUTHORIZATION,
+                HeaderValue::from_str(&format!("Basic {}", basic_auth))
+                    .expect("invariant: could not create basic auth header."),
+            );
+        }
+
+        #[cfg(any(feature = "tls", feature = "rustls-tls"))]
+        let use_rustls = self.uses_rustls();
+        #[cfg(any(feature = "tls", feature = "rustls-tls"))]
+        let tls_files = self.tls_files;
+        #[cfg(any(feature = "tls", feature = "rustls-tls"))]
+        let tls_material = match tls_files {
+            Some(ref files) => Some(files.load(use_rustls)?),
+            None => None,
+        };
+
+        let proxy_auth = self.proxy_auth;
+        let no_proxy = reqwest::NoProxy::from_string(&self.no_proxy.join(","));
+        let proxy = self.proxy.map(|proxy| {
+            let proxy = match proxy_auth {
+                Some(auth) => proxy.basic_auth(&auth.username, &auth.password),
+                None => proxy,
+            };
+            proxy.no_proxy(no_proxy)
+        });
+
+        let http_config = HttpConfig {
+            connect_timeout: self.connect_timeout,
+            tcp_keepalive: self.tcp_keepalive,
+            proxy,
+            #[cfg(any(feature = "tls", feature = "rustls-tls"))]
+            use_rustls,
+            #[cfg(any(feature = "tls", feature = "rustls-tls"))]
+            client_identity: self.tls_client_identity,
+            #[cfg(any(feature = "tls", feature = "rustls-tls"))]
+            root_certificates: self.tls_root_certificates,
+            #[cfg(any(feature = "tls", feature = "rustls-tls"))]
+            tls_material,
+        };
+
+        let transport: Arc<dyn Transport> = match self.transport {
+            Some(transport) => transport,
+            None => {
+                let http_client = http_config.build()?;
+                let transport = Arc::new(ReqwestTransport::new(http_client));
+
+                #[cfg(any(feature = "tls", feature = "rustls-tls"))]
+                if let (Some(files), Some(interval)) = (tls_files, self.tls_reload_interval) {
+                    tokio::spawn(tls::reload_tls_files(
+                        Arc::downgrade(transport.http_client()),
+                        http_config,
+                        files,
+                        interval,
+                    ));
+                }
+
+                transport
             }
-            None => client_builder,
         };
-        let client_builder = match self.request_timeout {
-            Some(timeout) => client_builder.timeout(timeout),
-            None => client_builder,
+
+        let endpoints = self
+            .endpoints
+            .into_iter()
+            .map(|uri| Arc::new(Endpoint::new(uri)))
+            .collect();
+        let client = Client {
+            endpoints: Arc::new(RwLock::new(endpoints)),
+            ejection_policy: self.ejection_policy,
+            retry_policy: self.retry_policy,
+            leader: if self.leader_routing {
+                Some(Arc::new(LeaderCache::default()))
+            } else {
+                None
+            },
+            request_options: RequestOptions::default(),
+            interceptors: self.interceptors,
+            metrics: self.metrics_sink.map(Metrics::new),
+            default_headers,
+            request_timeout: self.request_timeout,
+            transport,
         };
+
+        if let Some(interval) = self.auto_sync_interval {
+            tokio::spawn(auto_sync(client.downgrade(), interval));
+        }
+
+        if let Some(DnsSrv {
+            domain,
+            resolver,
+            refresh_interval: Some(interval),
+        }) = self.dns_srv
+        {
+            tokio::spawn(refresh_dns_srv(
+                Arc::downgrade(&client.endpoints),
+                domain,
+                resolver,
+                interval,
+            ));
+        }
+
+        Ok(client)
+    }
+}
+
+/// The settings a client's HTTP client is built with, kept to build it again when its TLS files
+/// change.
+#[derive(Clone, Debug)]
+struct HttpConfig {
+    connect_timeout: Duration,
+    tcp_keepalive: Option<Duration>,
+    proxy: Option<reqwest::Proxy>,
+    #[cfg(any(feature = "tls", feature = "rustls-tls"))]
+    use_rustls: bool,
+    #[cfg(any(feature = "tls", feature = "rustls-tls"))]
+    client_identity: Option<Identity>,
+    #[cfg(any(feature = "tls", feature = "rustls-tls"))]
+    root_certificates: Vec<Certificate>,
+    #[cfg(any(feature = "tls", feature = "rustls-tls"))]
+    tls_material: Option<TlsMaterial>,
+}
+
+impl HttpConfig {
+    /// Builds an HTTP client with the settings.
+    fn build(&self) -> Result<reqwest::Client, reqwest::Error> {
+        let client_builder = reqwest::ClientBuilder::new().connect_timeout(self.connect_timeout);
         let client_builder = match self.tcp_keepalive {
             Some(timeout) => client_builder.tcp_keepalive(timeout),
             None => client_builder,
         };
+        let client_builder = match self.proxy {
+            Some(ref proxy) => client_builder.proxy(proxy.clone()),
+            None => client_builder,
+        };
 
-        #[cfg(feature = "tls")]
+        #[cfg(feature = "rustls-tls")]
+        let client_builder = if self.use_rustls {
+            client_builder.use_rustls_tls()
+        } else {
+            client_builder
+        };
+
+        #[cfg(any(feature = "tls", feature = "rustls-tls"))]
         let client_builder = {
-            let client_builder = if let Some(identity) = self.tls_client_identity {
-                client_builder.identity(identity)
-            } else {
-                client_builder
+            let identity = match self.tls_material {
+                Some(ref material) => Some(&material.identity),
+                None => self.client_identity.as_ref(),
+            };
+            let client_builder = match identity {
+                Some(identity) => client_builder.identity(identity.clone()),
+                None => client_builder,
             };
 
-            self.tls_root_certificates
-                .into_iter()
-                .fold(client_builder, |client_builder, certificate| {
-                    client_builder.add_root_certificate(certificate)
-                })
+            let file_certificates = self
+                .tls_material
+                .iter()
+                .flat_map(|material| &material.root_certificates);
+            self.root_certificates.iter().chain(file_certificates).fold(
+                client_builder,
+                |client_builder, certificate| {
+                    client_builder.add_root_certificate(certificate.clone())
+                },
+            )
         };
 
-        let http_client = client_builder
-            .build()
-            .expect("invariant: could not create http client");
-
-        Client {
-            endpoints: Arc::new(self.endpoints),
-            http_client,
-        }
+        client_builder.build()
     }
 }
 
@@ -207,7 +714,7 @@ impl Client {
     /// # Parameters
     ///
     /// * endpoints: URLs for one or more cluster members. When making an API call, the client will
-    /// make the call to each member in order until it receives a successful respponse.
+    ///   make the call to each member in order until it receives a successful respponse.
     ///
     /// # Errors
     ///
@@ -216,47 +723,386 @@ impl Client {
         ClientBuilder::new(endpoints).build()
     }
 
-    /// Lets other internal code access the `HttpClient`.
-    pub(crate) fn http_client(&self) -> &reqwest::Client {
-        &self.http_client
+    /// Returns a copy of the client that makes API calls with the given options.
+    ///
+    /// The copy shares its endpoints and their health with this client, so it is cheap to create
+    /// one for each call that needs its own timeout, deadline, cancellation or headers.
+    pub fn with_request_options(&self, options: RequestOptions) -> Client {
+        Client {
+            request_options: options,
+            ..self.clone()
+        }
+    }
+
+    /// Lets other internal code build HTTP requests with the client's request options applied.
+    pub(crate) fn http_request<U>(&self, method: Method, url: U) -> RequestBuilder<'_>
+    where
+        U: AsRef<str>,
+    {
+        let request = Url::parse(url.as_ref())
+            .map(|url| HttpRequest {
+                method,
+                url,
+                headers: self.request_options.headers().clone(),
+                body: None,
+                timeout: self.request_options.timeout().or(self.request_timeout),
+            })
+            .map_err(Error::from);
+
+        RequestBuilder {
+            client: self,
+            request,
+        }
+    }
+
+    /// Runs the work for an API call, stopping it early if the call is cancelled or its deadline
+    /// passes.
+    async fn guard<R>(&self, work: impl Future<Output = R>) -> Result<R, Error> {
+        let options = &self.request_options;
+        if options.deadline().is_none() && options.cancellation().is_none() {
+            return Ok(work.await);
+        }
+
+        let deadline = async {
+            match options.deadline() {
+                Some(deadline) => tokio::time::sleep_until(deadline.into()).await,
+                None => future::pending().await,
+            }
+        };
+        let cancelled = async {
+            match options.cancellation() {
+                Some(cancellation) => cancellation.cancelled().await,
+                None => future::pending().await,
+            }
+        };
+        futures::pin_mut!(work, deadline, cancelled);
+
+        match future::select(work, future::select(deadline, cancelled)).await {
+            Either::Left((result, _)) => Ok(result),
+            Either::Right((Either::Left(_), _)) => Err(Error::DeadlineExceeded),
+            Either::Right((Either::Right(_), _)) => Err(Error::Cancelled),
+        }
     }
 
     /// Runs a basic health check against each etcd member.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "etcd.client.health", skip_all)
+    )]
     pub async fn health(&self) -> Vec<Result<Response<Health>, Error>> {
         self.request_on_each_endpoint("health").await
     }
 
     /// Returns version information from each etcd cluster member the client was initialized with.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "etcd.client.versions", skip_all)
+    )]
     pub async fn versions(&self) -> Vec<Result<Response<VersionInfo>, Error>> {
         self.request_on_each_endpoint("version").await
     }
 
-    fn shuffled_endpoints(&self) -> Vec<&Uri> {
-        // Shallow copy the endpoints, so we can shuffle them.
-        let mut endpoints: Vec<&Uri> = self.endpoints.iter().collect();
-        let mut rng = thread_rng();
-        endpoints.shuffle(&mut rng);
-        endpoints
+    /// Returns the etcd member endpoints the client currently makes requests to.
+    ///
+    /// The socket path of a `unix://` endpoint is hex-encoded as the URI's host.
+    pub fn endpoints(&self) -> Vec<Uri> {
+        self.current_endpoints()
+            .iter()
+            .map(|endpoint| endpoint.uri.clone())
+            .collect()
+    }
+
+    /// Returns the health of each etcd member endpoint, as observed by this client and its clones.
+    pub fn endpoint_health(&self) -> Vec<EndpointHealth> {
+        self.current_endpoints()
+            .iter()
+            .map(|endpoint| endpoint.health())
+            .collect()
+    }
+
+    /// Replaces the client's endpoints with the client URLs of the current cluster members.
+    ///
+    /// The new endpoints are used by this client and all of its clones. The health of endpoints
+    /// that were already in use is kept.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the members could not be listed, if any of their client URLs is invalid, or if no
+    /// member has a client URL. The endpoints are left unchanged in that case.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "etcd.client.sync_endpoints", skip_all)
+    )]
+    pub async fn sync_endpoints(&self) -> Result<(), Vec<Error>> {
+        let members = members::list(self).await?.data;
+
+        let mut uris: Vec<Uri> = Vec::new();
+        for url in members.iter().flat_map(|member| member.client_urls.iter()) {
+            let uri = unix::parse_endpoint(url).map_err(|error| vec![Error::InvalidUri(error)])?;
+            if !uris.contains(&uri) {
+                uris.push(uri);
+            }
+        }
+
+        if uris.is_empty() {
+            return Err(vec![Error::NoEndpoints]);
+        }
+
+        replace_endpoints(&self.endpoints, uris);
+        Ok(())
+    }
+
+    /// Returns the endpoints currently in use.
+    ///
+    /// The returned list is unaffected by endpoints being synced while it is used.
+    fn current_endpoints(&self) -> Vec<Arc<Endpoint>> {
+        self.endpoints
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Calls `handler` with each endpoint, healthiest first, until it returns successfully.
+    ///
+    /// If every endpoint fails, the calls are retried according to the client's retry policy, so
+    /// `handler` must only make idempotent or conditional requests. Returns the errors from the
+    /// last attempt if every attempt fails.
+    pub(crate) async fn first_ok<'a, H, F, T>(&'a self, handler: H) -> Result<T, Vec<Error>>
+    where
+        F: Future<Output = Result<T, Error>> + 'a,
+        H: Fn(&'a Client, &Uri) -> F,
+    {
+        self.first_ok_with(RequestKind::Idempotent, handler).await
+    }
+
+    /// Like `first_ok`, but retries and routes the calls according to the kind of request
+    /// `handler` makes.
+    ///
+    /// Fails with a single error if the client's request options cancel the call or its deadline
+    /// passes before any call succeeds.
+    pub(crate) async fn first_ok_with<'a, H, F, T>(
+        &'a self,
+        kind: RequestKind,
+        handler: H,
+    ) -> Result<T, Vec<Error>>
+    where
+        F: Future<Output = Result<T, Error>> + 'a,
+        H: Fn(&'a Client, &Uri) -> F,
+    {
+        let work = async {
+            let retry_policy = match self.retry_policy {
+                Some(ref retry_policy) if kind != RequestKind::NonIdempotentWrite => retry_policy,
+                _ => return self.attempt(kind, handler).await,
+            };
+
+            let start = Instant::now();
+            let mut attempt = 1;
+
+            loop {
+                let errors = match self.attempt(kind, &handler).await {
+                    Ok(response) => return Ok(response),
+                    Err(errors) => errors,
+                };
+
+                if attempt >= retry_policy.max_attempts() || !retry_policy.should_retry(&errors) {
+                    return Err(errors);
+                }
+
+                let backoff = retry_policy.backoff(attempt);
+                if let Some(deadline) = retry_policy.deadline() {
+                    if start.elapsed() + backoff >= deadline {
+                        return Err(errors);
+                    }
+                }
+
+                tokio::time::sleep(backoff).await;
+                attempt += 1;
+            }
+        };
+
+        match self.guard(work).await {
+            Ok(result) => result,
+            Err(error) => Err(vec![error]),
+        }
+    }
+
+    /// Makes a single attempt at a request of the given kind, trying each endpoint at most once.
+    async fn attempt<'a, H, F, T>(&'a self, kind: RequestKind, handler: H) -> Result<T, Vec<Error>>
+    where
+        F: Future<Output = Result<T, Error>> + 'a,
+        H: Fn(&'a Client, &Uri) -> F,
+    {
+        match kind {
+            RequestKind::Hedged(hedge_delay) => self.first_ok_hedged(hedge_delay, handler).await,
+            RequestKind::Idempotent => self.first_ok_once(false, handler).await,
+            RequestKind::Write | RequestKind::NonIdempotentWrite => {
+                self.first_ok_once(true, handler).await
+            }
+        }
     }
 
-    pub(crate) async fn first_ok<'a, H, F, T, E>(&'a self, handler: H) -> Result<T, Vec<E>>
+    /// Calls `handler` with each endpoint, healthiest first, until it returns successfully.
+    ///
+    /// Each endpoint is tried at most once. The health of each endpoint is updated with the result
+    /// of the call made with it. If `leader_first` is true and leader routing is enabled, the
+    /// leader's endpoint is tried before the others.
+    async fn first_ok_once<'a, H, F, T>(
+        &'a self,
+        leader_first: bool,
+        handler: H,
+    ) -> Result<T, Vec<Error>>
     where
-        F: Future<Output = Result<T, E>> + 'a,
-        H: Fn(&'a Client, &'a Uri) -> F,
+        F: Future<Output = Result<T, Error>> + 'a,
+        H: Fn(&'a Client, &Uri) -> F,
     {
+        let endpoints = self.current_endpoints();
+        let mut ordered = self.order_endpoints(&endpoints);
+
+        let leader = match self.leader {
+            Some(ref leader) if leader_first => Some(leader),
+            _ => None,
+        };
+        let mut leader_urls = Vec::new();
+        if let Some(leader) = leader {
+            leader_urls = leader.client_urls(self).await;
+            // A stable sort keeps the remaining endpoints in order of health.
+            ordered.sort_by_key(|endpoint| !leader_urls.contains(&endpoint.uri));
+        }
+
         let mut errors = Vec::new();
+        let last = ordered.len().saturating_sub(1);
 
-        for endpoint in self.shuffled_endpoints() {
-            let result = (handler)(&self, endpoint).await;
+        for (index, endpoint) in ordered.into_iter().enumerate() {
+            let result = self
+                .try_endpoint(&endpoint.uri, (handler)(self, &endpoint.uri))
+                .await;
+            endpoint.record(&result, self.ejection_policy);
             match result {
                 Ok(response) => return Ok(response),
-                Err(err) => errors.push(err),
+                Err(err) => {
+                    if let Some(leader) = leader {
+                        if leader_urls.contains(&endpoint.uri) && health::is_endpoint_failure(&err)
+                        {
+                            leader.invalidate();
+                        }
+                    }
+                    if index < last {
+                        self.record_failover(&endpoint.uri);
+                    }
+                    errors.push(err);
+                }
             }
         }
 
         Err(errors)
     }
 
+    /// Like `first_ok_once`, but calls `handler` with the next endpoint whenever the calls in
+    /// progress have not returned within `hedge_delay`, or as soon as they have all failed.
+    ///
+    /// Returns the result of the first successful call, cancelling the others. Calls that are
+    /// cancelled don't affect the health of their endpoints.
+    async fn first_ok_hedged<'a, H, F, T>(
+        &'a self,
+        hedge_delay: Duration,
+        handler: H,
+    ) -> Result<T, Vec<Error>>
+    where
+        F: Future<Output = Result<T, Error>> + 'a,
+        H: Fn(&'a Client, &Uri) -> F,
+    {
+        let endpoints = self.current_endpoints();
+        let ordered = self.order_endpoints(&endpoints);
+        let mut remaining = 0..ordered.len();
+        let mut in_progress = FuturesUnordered::new();
+        let mut errors = Vec::new();
+
+        let call = |index: usize| {
+            let endpoint = ordered[index];
+            let future = self.try_endpoint(&endpoint.uri, (handler)(self, &endpoint.uri));
+            async move { (endpoint, future.await) }
+        };
+
+        loop {
+            if in_progress.is_empty() {
+                match remaining.next() {
+                    Some(index) => in_progress.push(call(index)),
+                    None => return Err(errors),
+                }
+            }
+
+            let hedge = tokio::time::sleep(hedge_delay);
+            futures::pin_mut!(hedge);
+
+            match future::select(in_progress.next(), hedge).await {
+                Either::Left((Some((endpoint, result)), _)) => {
+                    endpoint.record(&result, self.ejection_policy);
+                    match result {
+                        Ok(response) => return Ok(response),
+                        Err(err) => {
+                            if in_progress.is_empty() && !remaining.is_empty() {
+                                self.record_failover(&endpoint.uri);
+                            }
+                            errors.push(err);
+                        }
+                    }
+                }
+                Either::Left((None, _)) => {}
+                Either::Right(_) => {
+                    if let Some(index) = remaining.next() {
+                        in_progress.push(call(index));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Orders endpoints for a request according to their health, starting a probe of an ejected
+    /// endpoint whose cooldown has passed.
+    fn order_endpoints<'e>(&self, endpoints: &'e [Arc<Endpoint>]) -> Vec<&'e Endpoint> {
+        let (ordered, probe) = health::order(endpoints, self.ejection_policy);
+        if let Some(endpoint) = probe {
+            // The probe must not keep the client's endpoints alive.
+            let client = self.downgrade().client;
+            tokio::spawn(probe_endpoint(client, endpoint));
+        }
+        ordered
+    }
+
+    /// Makes a request to a single endpoint, recording it in the client's traces and metrics.
+    async fn try_endpoint<F, T>(&self, endpoint: &Uri, request: F) -> Result<T, Error>
+    where
+        F: Future<Output = Result<T, Error>>,
+    {
+        let result = trace::attempt(endpoint, request).await;
+        if let (Some(ref metrics), Err(ref error)) = (&self.metrics, &result) {
+            metrics.sink().record_error(endpoint, error);
+        }
+        result
+    }
+
+    /// Records that a call is moving on to the next endpoint after the attempt on `endpoint`
+    /// failed.
+    fn record_failover(&self, endpoint: &Uri) {
+        if let Some(ref metrics) = self.metrics {
+            metrics.sink().record_failover(endpoint);
+        }
+    }
+
+    /// Counts a watch as open in the client's metrics until the returned guard is dropped.
+    pub(crate) fn open_watch(&self) -> Option<OpenWatch> {
+        self.metrics.as_ref().map(Metrics::open_watch)
+    }
+
+    /// Updates the client's view of the cluster with the information from a response.
+    ///
+    /// Forgets the cached leader if the response shows that a new leader may have been elected.
+    pub(crate) fn observe_cluster_info(&self, cluster_info: &ClusterInfo) {
+        if let Some(ref leader) = self.leader {
+            leader.observe(cluster_info);
+        }
+    }
+
     /// Attempts to issue a GET request to the given path on all endpoints, returning the result of the first successful request.
     pub(crate) async fn request_first_ok<T, P>(&self, path: P) -> Result<Response<T>, Error>
     where
@@ -287,10 +1133,18 @@ impl Client {
         T: DeserializeOwned,
     {
         let path = path.as_ref();
-        let mut results = Vec::with_capacity(self.endpoints.len());
+        let endpoints = self.current_endpoints();
+        let mut results = Vec::with_capacity(endpoints.len());
 
-        for endpoint in self.endpoints.iter() {
-            let result = self.request(build_url(endpoint, path)).await;
+        for endpoint in endpoints.iter() {
+            let request = self.request(build_url(&endpoint.uri, path));
+            let result = match self.guard(self.try_endpoint(&endpoint.uri, request)).await {
+                Ok(result) => {
+                    endpoint.record(&result, self.ejection_policy);
+                    result
+                }
+                Err(error) => Err(error),
+            };
             results.push(result);
         }
 
@@ -300,24 +1154,232 @@ impl Client {
     /// Lets other internal code make basic HTTP requests.
     pub(crate) async fn request<T, U>(&self, uri: U) -> Result<Response<T>, Error>
     where
-        U: IntoUrl,
+        U: AsRef<str>,
         T: DeserializeOwned,
     {
-        let response = self.http_client.get(uri).send().await?;
+        let response = self.http_request(Method::GET, uri).send().await?;
         parse_etcd_response(response, |s| s == StatusCode::OK).await
     }
 }
 
+/// An HTTP request being built by internal code, which is sent through the client's interceptors
+/// and transport.
+#[derive(Debug)]
+pub(crate) struct RequestBuilder<'a> {
+    /// The client sending the request.
+    client: &'a Client,
+    /// The request, or the error building it.
+    request: Result<HttpRequest, Error>,
+}
+
+impl RequestBuilder<'_> {
+    /// Adds a header to the request.
+    pub(crate) fn header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        if let Ok(ref mut request) = self.request {
+            request.headers.append(name, value);
+        }
+        self
+    }
+
+    /// Sets the body of the request.
+    pub(crate) fn body(mut self, body: impl Into<Bytes>) -> Self {
+        if let Ok(ref mut request) = self.request {
+            request.body = Some(body.into());
+        }
+        self
+    }
+
+    /// Lets the request take as long as it needs, ignoring the client's request timeout.
+    pub(crate) fn without_timeout(mut self) -> Self {
+        if let Ok(ref mut request) = self.request {
+            request.timeout = None;
+        }
+        self
+    }
+
+    /// Sends the request with the client's transport, calling the client's interceptors before
+    /// and after, and recording it in the client's metrics.
+    ///
+    /// Returns once the response headers have been received, so the time recorded for a request
+    /// doesn't include receiving its body, such as the event a watch waits for.
+    pub(crate) async fn send(self) -> Result<HttpResponse, Error> {
+        let mut request = self.request?;
+        let client = self.client;
+        for (name, value) in &client.default_headers {
+            if let Entry::Vacant(entry) = request.headers.entry(name) {
+                entry.insert(value.clone());
+            }
+        }
+
+        if client.interceptors.is_empty() && client.metrics.is_none() {
+            return client.transport.send(request).await;
+        }
+
+        for interceptor in &client.interceptors {
+            interceptor.before_request(&mut InterceptedRequest::new(&mut request));
+        }
+
+        let method = request.method.clone();
+        let url = request.url.clone();
+        let start = Instant::now();
+        let result = client.transport.send(request).await;
+        let elapsed = start.elapsed();
+
+        let status = result.as_ref().ok().map(|response| response.status);
+        if let Some(ref metrics) = client.metrics {
+            metrics.record_request(&method, &url, status, elapsed);
+        }
+
+        if !client.interceptors.is_empty() {
+            let outcome = RequestOutcome {
+                method: &method,
+                url: &url,
+                status,
+                cluster_info: result
+                    .as_ref()
+                    .ok()
+                    .map(|response| ClusterInfo::from(&response.headers)),
+                elapsed,
+            };
+            for interceptor in &client.interceptors {
+                interceptor.after_request(&outcome);
+            }
+        }
+
+        result
+    }
+}
+
+/// The kind of request made by a handler passed to `Client::first_ok_with`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum RequestKind {
+    /// A read, or a write that has the same effect however many times it is made. Retried
+    /// according to the client's retry policy.
+    Idempotent,
+    /// A write to the key space that is idempotent or conditional. Retried according to the
+    /// client's retry policy and sent to the leader first if leader routing is enabled.
+    Write,
+    /// A write that has a different effect each time it is made. Never retried, as a request that
+    /// appeared to fail may still have been applied, and sent to the leader first if leader
+    /// routing is enabled.
+    NonIdempotentWrite,
+    /// An idempotent read that is also sent to the next endpoint whenever no response has arrived
+    /// within the given delay. Retried according to the client's retry policy.
+    Hedged(Duration),
+}
+
+/// A client that does not keep its endpoints alive, for use by background tasks.
+#[derive(Debug)]
+struct WeakClient {
+    /// The client's endpoints.
+    endpoints: Weak<RwLock<Vec<Arc<Endpoint>>>>,
+    /// A copy of the client with placeholder endpoints.
+    client: Client,
+}
+
+impl Client {
+    /// Returns a copy of the client that does not keep its endpoints alive.
+    fn downgrade(&self) -> WeakClient {
+        WeakClient {
+            endpoints: Arc::downgrade(&self.endpoints),
+            client: Client {
+                endpoints: Arc::default(),
+                ..self.clone()
+            },
+        }
+    }
+}
+
+impl WeakClient {
+    /// Returns the client, unless it and all of its clones have been dropped.
+    fn upgrade(&self) -> Option<Client> {
+        self.endpoints.upgrade().map(|endpoints| Client {
+            endpoints,
+            ..self.client.clone()
+        })
+    }
+}
+
+/// Sends a health check to an ejected endpoint, which is healthy again if the check succeeds.
+///
+/// A check that has not completed within the ejection cooldown is abandoned, leaving the endpoint
+/// ejected until it is probed again.
+async fn probe_endpoint(client: Client, endpoint: Arc<Endpoint>) {
+    let policy = client.ejection_policy;
+    let request = client.request::<Health, _>(build_url(&endpoint.uri, "health"));
+    let probe = client.try_endpoint(&endpoint.uri, request);
+
+    if let Ok(result) = tokio::time::timeout(policy.cooldown, probe).await {
+        endpoint.record(&result, policy);
+    }
+}
+
+/// Syncs the endpoints of a client every `interval` until the client and its clones are dropped.
+async fn auto_sync(client: WeakClient, interval: Duration) {
+    loop {
+        tokio::time::sleep(interval).await;
+
+        let client = match client.upgrade() {
+            Some(client) => client,
+            None => return,
+        };
+
+        if let Err(errors) = client.sync_endpoints().await {
+            error!("failed to sync endpoints: {:?}", errors);
+        }
+    }
+}
+
+/// Looks up the DNS SRV records of `domain` every `interval`, replacing the endpoints of a client
+/// with the records found, until the client and its clones are dropped.
+async fn refresh_dns_srv(
+    endpoints: Weak<RwLock<Vec<Arc<Endpoint>>>>,
+    domain: String,
+    resolver: Arc<dyn SrvResolver>,
+    interval: Duration,
+) {
+    loop {
+        tokio::time::sleep(interval).await;
+
+        let result = dns::resolve_endpoints(&*resolver, &domain).await;
+        let endpoints = match endpoints.upgrade() {
+            Some(endpoints) => endpoints,
+            None => return,
+        };
+
+        match result {
+            Ok(uris) => replace_endpoints(&endpoints, uris),
+            Err(error) => error!("failed to resolve endpoints for {}: {}", domain, error),
+        }
+    }
+}
+
+/// Replaces a client's endpoints, keeping the health of endpoints that were already in use.
+fn replace_endpoints(endpoints: &RwLock<Vec<Arc<Endpoint>>>, uris: Vec<Uri>) {
+    let mut endpoints = endpoints.write().unwrap_or_else(PoisonError::into_inner);
+    let replaced = uris
+        .into_iter()
+        .map(|uri| {
+            endpoints
+                .iter()
+                .find(|endpoint| endpoint.uri == uri)
+                .cloned()
+                .unwrap_or_else(|| Arc::new(Endpoint::new(uri)))
+        })
+        .collect();
+    *endpoints = replaced;
+}
+
 pub(crate) async fn parse_etcd_response<T>(
-    response: reqwest::Response,
+    response: HttpResponse,
     status_code_is_success: impl FnOnce(StatusCode) -> bool,
 ) -> Result<Response<T>, Error>
 where
     T: DeserializeOwned,
 {
-    let status_code = response.status();
-    let cluster_info = ClusterInfo::from(response.headers());
-    let body = response.bytes().await?;
+    let status_code = response.status;
+    let cluster_info = response_cluster_info(&response);
+    let body = response.body.bytes().await?;
     if status_code_is_success(status_code) {
         match serde_json::from_slice::<T>(&body) {
             Ok(data) => Ok(Response { data, cluster_info }),
@@ -416,12 +1478,10 @@ impl<'a> From<&'a HeaderMap<HeaderValue>> for ClusterInfo {
     }
 }
 
-pub(crate) async fn parse_empty_response(
-    response: reqwest::Response,
-) -> Result<Response<()>, Error> {
-    let status_code = response.status();
-    let cluster_info = ClusterInfo::from(response.headers());
-    let body = response.bytes().await?;
+pub(crate) async fn parse_empty_response(response: HttpResponse) -> Result<Response<()>, Error> {
+    let status_code = response.status;
+    let cluster_info = response_cluster_info(&response);
+    let body = response.body.bytes().await?;
     match status_code {
         StatusCode::NO_CONTENT | StatusCode::OK => Ok(Response {
             data: (),
@@ -434,6 +1494,14 @@ pub(crate) async fn parse_empty_response(
     }
 }
 
+/// Returns the cluster information from a response's headers, recording it along with the
+/// response's status on the span of the endpoint the response came from.
+pub(crate) fn response_cluster_info(response: &HttpResponse) -> ClusterInfo {
+    let cluster_info = ClusterInfo::from(&response.headers);
+    trace::record_response(response.status, &cluster_info);
+    cluster_info
+}
+
 /// Constructs the full URL for the versions API call.
 fn build_url(endpoint: &Uri, path: &str) -> String {
     format!("{}{}", endpoint, path)
@@ -0,0 +1,182 @@
+//! Tracks the health of each cluster member endpoint a `Client` makes requests to.
+//!
+//! An endpoint that fails a request is tried after healthy endpoints until it succeeds again.
+//! Once it fails enough requests in a row, it is ejected and skipped entirely for a cooldown
+//! period. After the cooldown, it is probed in the background with a health check, so that no
+//! request is sent to it while it may still be down: if the probe succeeds, the endpoint is
+//! healthy again, otherwise it is ejected for another cooldown period.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use http::Uri;
+use rand::{prelude::SliceRandom, thread_rng};
+
+use crate::error::Error;
+
+/// The number of consecutive failed requests after which an endpoint is ejected, by default.
+pub(crate) const DEFAULT_FAILURE_THRESHOLD: u32 = 3;
+
+/// How long an ejected endpoint is skipped before it is probed again, by default.
+pub(crate) const DEFAULT_EJECTION_COOLDOWN: Duration = Duration::from_secs(30);
+
+/// The health of a cluster member endpoint, as observed by a `Client`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct EndpointHealth {
+    /// The endpoint's URL.
+    pub endpoint: Uri,
+    /// The number of requests to the endpoint that have failed since the last successful one.
+    pub consecutive_failures: u32,
+    /// A description of the last failed request to the endpoint.
+    pub last_error: Option<String>,
+    /// When the endpoint was last ejected or probed, if it is currently ejected.
+    pub ejected_at: Option<Instant>,
+}
+
+/// When to eject failing endpoints and for how long.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub(crate) struct EjectionPolicy {
+    /// The number of consecutive failed requests after which an endpoint is ejected.
+    pub(crate) failure_threshold: u32,
+    /// How long an ejected endpoint is skipped before it is probed again.
+    pub(crate) cooldown: Duration,
+}
+
+/// A cluster member endpoint along with its health.
+#[derive(Debug)]
+pub(crate) struct Endpoint {
+    /// The endpoint's URL.
+    pub(crate) uri: Uri,
+    /// The endpoint's health, updated after each request.
+    state: Mutex<State>,
+}
+
+/// The mutable health state of an endpoint.
+#[derive(Clone, Debug, Default)]
+struct State {
+    /// The number of requests that have failed since the last successful one.
+    consecutive_failures: u32,
+    /// A description of the last failed request.
+    last_error: Option<String>,
+    /// When the endpoint was last ejected or probed, if it is currently ejected.
+    ejected_at: Option<Instant>,
+}
+
+impl Default for EjectionPolicy {
+    fn default() -> Self {
+        EjectionPolicy {
+            failure_threshold: DEFAULT_FAILURE_THRESHOLD,
+            cooldown: DEFAULT_EJECTION_COOLDOWN,
+        }
+    }
+}
+
+impl Endpoint {
+    /// Creates a new, healthy endpoint.
+    pub(crate) fn new(uri: Uri) -> Self {
+        Endpoint {
+            uri,
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    /// Returns the current health of the endpoint.
+    pub(crate) fn health(&self) -> EndpointHealth {
+        let state = self.state();
+        EndpointHealth {
+            endpoint: self.uri.clone(),
+            consecutive_failures: state.consecutive_failures,
+            last_error: state.last_error.clone(),
+            ejected_at: state.ejected_at,
+        }
+    }
+
+    /// Updates the endpoint's health with the outcome of a request made to it.
+    ///
+    /// Errors returned by etcd about the request itself, such as a missing key, show that the
+    /// endpoint is working and count as successes.
+    pub(crate) fn record<T>(&self, result: &Result<T, Error>, policy: EjectionPolicy) {
+        let mut state = self.state();
+
+        match *result {
+            Err(ref error) if is_endpoint_failure(error) => {
+                state.consecutive_failures = state.consecutive_failures.saturating_add(1);
+                state.last_error = Some(error.to_string());
+                if state.consecutive_failures >= policy.failure_threshold {
+                    state.ejected_at = Some(Instant::now());
+                }
+            }
+            _ => {
+                state.consecutive_failures = 0;
+                state.ejected_at = None;
+            }
+        }
+    }
+
+    /// Locks the endpoint's health state.
+    fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        // The state is always left consistent, so it is still usable if a thread panicked while
+        // holding the lock.
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Orders endpoints for a request according to their health.
+///
+/// Healthy endpoints come first in random order, followed by endpoints that have recently failed,
+/// least failures first. Ejected endpoints are skipped. If every endpoint is ejected, they are all
+/// tried anyway, least recently ejected first.
+///
+/// Also returns an ejected endpoint whose cooldown has passed, if there is one, for the caller to
+/// probe. Its cooldown is restarted so that concurrent requests don't probe it as well.
+pub(crate) fn order(
+    endpoints: &[Arc<Endpoint>],
+    policy: EjectionPolicy,
+) -> (Vec<&Endpoint>, Option<Arc<Endpoint>>) {
+    let now = Instant::now();
+    let mut probe = None;
+    let mut healthy = Vec::new();
+    let mut failing = Vec::new();
+    let mut ejected = Vec::new();
+
+    for endpoint in endpoints {
+        let mut state = endpoint.state();
+        match state.ejected_at {
+            Some(ejected_at) => {
+                if probe.is_none() && now - ejected_at >= policy.cooldown {
+                    state.ejected_at = Some(now);
+                    probe = Some(endpoint.clone());
+                }
+                ejected.push((ejected_at, &**endpoint));
+            }
+            None if state.consecutive_failures == 0 => healthy.push(&**endpoint),
+            None => failing.push((state.consecutive_failures, &**endpoint)),
+        }
+    }
+
+    healthy.shuffle(&mut thread_rng());
+    failing.sort_by_key(|&(failures, _)| failures);
+
+    let mut ordered = healthy;
+    ordered.extend(failing.into_iter().map(|(_, endpoint)| endpoint));
+
+    if ordered.is_empty() {
+        ejected.sort_by_key(|&(ejected_at, _)| ejected_at);
+        ordered.extend(ejected.into_iter().map(|(_, endpoint)| endpoint));
+    }
+
+    (ordered, probe)
+}
+
+/// Returns whether an error indicates a problem with the endpoint rather than with the request.
+pub(crate) fn is_endpoint_failure(error: &Error) -> bool {
+    match *error {
+        Error::Http(_) | Error::Transport(_) | Error::UnixSocket(_) => true,
+        // Error codes in the 300s are raft and etcd server internal errors.
+        Error::Api(ref error) => (300..400).contains(&error.error_code),
+        Error::UnexpectedStatus(status) => status.is_server_error(),
+        _ => false,
+    }
+}
@@ -0,0 +1,93 @@
+//! Hooks that observe and modify the HTTP requests a `Client` makes.
+
+use std::fmt::Debug;
+use std::time::Duration;
+
+use http::header::HeaderMap;
+use http::{Method, StatusCode};
+use url::Url;
+
+use crate::client::{ClusterInfo, HttpRequest};
+
+/// Observes and modifies the HTTP requests made by a `Client`.
+///
+/// Interceptors are registered with `ClientBuilder::with_interceptor` and called for every request
+/// made to an etcd endpoint, whether it comes from the `kv`, `auth`, `members` or `stats` module,
+/// in the order they were registered.
+pub trait Interceptor: Debug + Send + Sync {
+    /// Called before a request is sent. The request's URL and headers may be modified.
+    ///
+    /// The default implementation does nothing.
+    fn before_request(&self, request: &mut InterceptedRequest<'_>) {
+        let _ = request;
+    }
+
+    /// Called once the response headers to a request have been received, or once the request has
+    /// failed without a response.
+    ///
+    /// The default implementation does nothing.
+    fn after_request(&self, outcome: &RequestOutcome<'_>) {
+        let _ = outcome;
+    }
+}
+
+/// An HTTP request about to be sent to an etcd endpoint.
+#[derive(Debug)]
+pub struct InterceptedRequest<'a> {
+    /// The request, as it will be sent.
+    request: &'a mut HttpRequest,
+}
+
+impl<'a> InterceptedRequest<'a> {
+    /// Wraps a request for interceptors.
+    pub(crate) fn new(request: &'a mut HttpRequest) -> Self {
+        InterceptedRequest { request }
+    }
+
+    /// Returns the request's HTTP method.
+    pub fn method(&self) -> &Method {
+        &self.request.method
+    }
+
+    /// Returns the URL the request will be sent to.
+    pub fn url(&self) -> &Url {
+        &self.request.url
+    }
+
+    /// Changes the URL the request will be sent to, for example to go through a proxy.
+    pub fn set_url(&mut self, url: Url) {
+        self.request.url = url;
+    }
+
+    /// Returns the request's headers.
+    pub fn headers(&self) -> &HeaderMap {
+        &self.request.headers
+    }
+
+    /// Returns the request's headers for modification.
+    pub fn headers_mut(&mut self) -> &mut HeaderMap {
+        &mut self.request.headers
+    }
+
+    /// Returns the request's body, if it has one.
+    pub fn body(&self) -> Option<&[u8]> {
+        self.request.body.as_deref()
+    }
+}
+
+/// The outcome of an HTTP request made to an etcd endpoint.
+#[derive(Clone, Debug)]
+pub struct RequestOutcome<'a> {
+    /// The request's HTTP method.
+    pub method: &'a Method,
+    /// The URL the request was sent to.
+    pub url: &'a Url,
+    /// The response's HTTP status code, or `None` if the request failed without a response.
+    pub status: Option<StatusCode>,
+    /// Information about the state of the cluster from the response's headers, or `None` if the
+    /// request failed without a response.
+    pub cluster_info: Option<ClusterInfo>,
+    /// How long it took from sending the request until the response headers were received, or
+    /// until the request failed.
+    pub elapsed: Duration,
+}
@@ -0,0 +1,183 @@
+//! Finding the cluster leader, so that writes can be sent to it directly.
+//!
+//! Followers proxy writes to the leader, which costs an extra network hop. When leader routing is
+//! enabled, the client finds the leader through the statistics API, maps it to its client URLs
+//! through the members API, and caches the result until the raft term changes.
+//!
+//! etcd only reports the raft term on responses from the keys API, so the term a leader was found
+//! in is unknown until the first such response, whose term is then adopted.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use http::Uri;
+use log::{error, warn};
+
+use crate::client::{unix, Client, ClusterInfo};
+use crate::{members, stats};
+
+/// The state reported in the self statistics of the leader.
+const LEADER_STATE: &str = "StateLeader";
+
+/// How long to wait before trying to find the leader again after failing to.
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// The leader of the cluster, as last found by a client and its clones.
+#[derive(Debug, Default)]
+pub(crate) struct LeaderCache {
+    /// The cached leader.
+    state: Mutex<State>,
+    /// Held while the leader is being found, so that concurrent callers wait for a single lookup
+    /// rather than each making their own.
+    lookup: tokio::sync::Mutex<()>,
+}
+
+/// The mutable state of a `LeaderCache`.
+#[derive(Debug, Default)]
+struct State {
+    /// The leader, if it is known.
+    leader: Option<Leader>,
+    /// When finding the leader last failed.
+    failed_at: Option<Instant>,
+}
+
+/// A cluster leader.
+#[derive(Clone, Debug)]
+struct Leader {
+    /// The client URLs of the leader.
+    client_urls: Vec<Uri>,
+    /// The raft term the leader was found in, if it is known yet.
+    raft_term: Option<u64>,
+}
+
+impl LeaderCache {
+    /// Returns the client URLs of the leader, finding it if it is not cached.
+    ///
+    /// Returns an empty list if the leader could not be found.
+    pub(crate) async fn client_urls(&self, client: &Client) -> Vec<Uri> {
+        if let Some(client_urls) = self.cached_client_urls() {
+            return client_urls;
+        }
+
+        // Another caller may have found the leader, or failed to, while this one waited.
+        let _lookup = self.lookup.lock().await;
+        if let Some(client_urls) = self.cached_client_urls() {
+            return client_urls;
+        }
+
+        // Finding the leader makes requests through the client, so the future must be boxed.
+        let leader = Box::pin(find_leader(client)).await;
+        let mut state = self.state();
+        match leader {
+            Some(leader) => {
+                let endpoints = client.endpoints();
+                if !leader.client_urls.iter().any(|url| endpoints.contains(url)) {
+                    warn!(
+                        "the cluster leader's client URLs {:?} match none of the client's \
+                         endpoints, so writes are not routed to it",
+                        leader.client_urls
+                    );
+                }
+                let client_urls = leader.client_urls.clone();
+                state.leader = Some(leader);
+                state.failed_at = None;
+                client_urls
+            }
+            None => {
+                state.failed_at = Some(Instant::now());
+                Vec::new()
+            }
+        }
+    }
+
+    /// Returns the client URLs of the cached leader, or an empty list if finding the leader failed
+    /// too recently to try again.
+    fn cached_client_urls(&self) -> Option<Vec<Uri>> {
+        let state = self.state();
+        if let Some(ref leader) = state.leader {
+            return Some(leader.client_urls.clone());
+        }
+        match state.failed_at {
+            Some(failed_at) if failed_at.elapsed() < RETRY_DELAY => Some(Vec::new()),
+            _ => None,
+        }
+    }
+
+    /// Forgets the cached leader if a response shows that the raft term has changed since it was
+    /// found.
+    ///
+    /// If the term the leader was found in is not known yet, the response's term is adopted.
+    pub(crate) fn observe(&self, cluster_info: &ClusterInfo) {
+        let mut state = self.state();
+        let changed = match (&mut state.leader, cluster_info.raft_term) {
+            (Some(leader), Some(raft_term)) => match leader.raft_term {
+                Some(leader_term) => leader_term != raft_term,
+                None => {
+                    leader.raft_term = Some(raft_term);
+                    false
+                }
+            },
+            _ => false,
+        };
+
+        if changed {
+            state.leader = None;
+        }
+    }
+
+    /// Forgets the cached leader, for example because it stopped responding.
+    pub(crate) fn invalidate(&self) {
+        self.state().leader = None;
+    }
+
+    /// Locks the cache's state.
+    fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Finds the leader through the statistics and members APIs.
+async fn find_leader(client: &Client) -> Option<Leader> {
+    let leader = stats::self_stats(client)
+        .await
+        .into_iter()
+        .filter_map(Result::ok)
+        .find(|response| response.data.state == LEADER_STATE)
+        .map(|response| (response.data.id, response.cluster_info.raft_term));
+
+    let (id, raft_term) = match leader {
+        Some(leader) => leader,
+        None => match stats::leader_stats(client).await {
+            Ok(response) => (response.data.leader, response.cluster_info.raft_term),
+            Err(error) => {
+                error!("failed to find the cluster leader: {}", error);
+                return None;
+            }
+        },
+    };
+
+    let members = match members::list(client).await {
+        Ok(response) => response.data,
+        Err(errors) => {
+            error!(
+                "failed to list members to find the cluster leader: {:?}",
+                errors
+            );
+            return None;
+        }
+    };
+
+    let member = members.into_iter().find(|member| member.id == id)?;
+    let client_urls = member
+        .client_urls
+        .iter()
+        .filter_map(|url| unix::parse_endpoint(url).ok())
+        .collect();
+
+    Some(Leader {
+        client_urls,
+        raft_term,
+    })
+}
@@ -0,0 +1,140 @@
+//! Hooks for recording metrics about the requests a `Client` makes.
+
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use http::{Method, StatusCode, Uri};
+use url::{Position, Url};
+
+use crate::error::Error;
+
+/// Records metrics about the requests made by a `Client`.
+///
+/// A sink is registered with `ClientBuilder::with_metrics_sink`, and is shared by the client and
+/// all of its clones. Every method has a default implementation that does nothing, so a sink only
+/// needs to implement the methods for the metrics it records.
+pub trait MetricsSink: Debug + Send + Sync {
+    /// Records an HTTP request made to an endpoint, once its response headers have been received
+    /// or it has failed without a response.
+    fn record_request(&self, request: &RequestMetrics<'_>) {
+        let _ = request;
+    }
+
+    /// Records an error returned by an attempt to make an API call on an endpoint.
+    ///
+    /// Unlike `record_request`, this includes errors returned by etcd, such as a missing key, and
+    /// errors parsing the response.
+    fn record_error(&self, endpoint: &Uri, error: &Error) {
+        let _ = (endpoint, error);
+    }
+
+    /// Records that an API call moved on to the next endpoint because the attempt on `endpoint`
+    /// failed.
+    fn record_failover(&self, endpoint: &Uri) {
+        let _ = endpoint;
+    }
+
+    /// Records the number of watches that are currently waiting for a change, across the client
+    /// and all of its clones. Called whenever a watch starts or stops waiting.
+    fn record_open_watches(&self, open_watches: usize) {
+        let _ = open_watches;
+    }
+}
+
+/// An HTTP request made to an endpoint, as recorded by a `MetricsSink`.
+#[derive(Clone, Debug)]
+pub struct RequestMetrics<'a> {
+    /// The scheme, host and port of the endpoint the request was made to, such as
+    /// `http://127.0.0.1:2379`.
+    pub endpoint: &'a str,
+    /// The request's HTTP method.
+    pub method: &'a Method,
+    /// The etcd API the request was made to: `keys`, `members`, `auth`, `stats`, `health` or
+    /// `version`.
+    pub api: &'a str,
+    /// The response's HTTP status code, or `None` if the request failed without a response.
+    pub status: Option<StatusCode>,
+    /// How long it took from sending the request until the response headers were received, or
+    /// until the request failed.
+    pub elapsed: Duration,
+}
+
+/// A client's metrics sink, along with the state needed to compute the metrics it records.
+#[derive(Clone, Debug)]
+pub(crate) struct Metrics {
+    /// The sink metrics are recorded to.
+    sink: Arc<dyn MetricsSink>,
+    /// The number of watches that are currently waiting for a change.
+    open_watches: Arc<AtomicUsize>,
+}
+
+/// Decrements the number of open watches when dropped.
+#[derive(Debug)]
+pub(crate) struct OpenWatch {
+    /// The metrics the watch was counted in.
+    metrics: Metrics,
+}
+
+impl Metrics {
+    /// Creates the metrics for a client.
+    pub(crate) fn new(sink: Arc<dyn MetricsSink>) -> Self {
+        Metrics {
+            sink,
+            open_watches: Arc::default(),
+        }
+    }
+
+    /// Returns the sink metrics are recorded to.
+    pub(crate) fn sink(&self) -> &dyn MetricsSink {
+        &*self.sink
+    }
+
+    /// Records an HTTP request made to `url`.
+    pub(crate) fn record_request(
+        &self,
+        method: &Method,
+        url: &Url,
+        status: Option<StatusCode>,
+        elapsed: Duration,
+    ) {
+        // The origin of a `unix://` endpoint is opaque, so its scheme and host are used instead.
+        let origin = url.origin();
+        let endpoint = if origin.is_tuple() {
+            origin.ascii_serialization()
+        } else {
+            url[..Position::BeforePath].to_owned()
+        };
+        let mut segments = url.path().trim_start_matches('/').split('/');
+        let api = match segments.next() {
+            Some("v2") => segments.next().unwrap_or(""),
+            Some(segment) => segment,
+            None => "",
+        };
+
+        self.sink.record_request(&RequestMetrics {
+            endpoint: &endpoint,
+            method,
+            api,
+            status,
+            elapsed,
+        });
+    }
+
+    /// Counts a watch as open until the returned guard is dropped.
+    pub(crate) fn open_watch(&self) -> OpenWatch {
+        let open_watches = self.open_watches.fetch_add(1, Ordering::SeqCst) + 1;
+        self.sink.record_open_watches(open_watches);
+        OpenWatch {
+            metrics: self.clone(),
+        }
+    }
+}
+
+impl Drop for OpenWatch {
+    fn drop(&mut self) {
+        let open_watches = self.metrics.open_watches.fetch_sub(1, Ordering::SeqCst) - 1;
+        self.metrics.sink.record_open_watches(open_watches);
+    }
+}
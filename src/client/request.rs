@@ -0,0 +1,135 @@
+//! Options that apply to individual API calls rather than to a whole client.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use http::header::{HeaderMap, HeaderName, HeaderValue};
+use tokio::sync::Notify;
+
+/// Options for the API calls made with a client returned by `Client::with_request_options`.
+///
+/// Every function in the `kv`, `auth`, `members` and `stats` modules takes a client, so passing
+/// such a client to one of them makes that call with these options.
+#[derive(Clone, Debug, Default)]
+pub struct RequestOptions {
+    /// How long each HTTP request may take.
+    timeout: Option<Duration>,
+    /// When the whole call must have completed by.
+    deadline: Option<Instant>,
+    /// A token that cancels the call.
+    cancellation: Option<CancellationToken>,
+    /// Headers added to each HTTP request.
+    headers: HeaderMap,
+}
+
+impl RequestOptions {
+    /// Creates options with no timeout, deadline, cancellation or extra headers.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Configures how long each HTTP request made for a call may take.
+    ///
+    /// The timeout applies to the request sent to each endpoint separately, and replaces the
+    /// client's request timeout. Like the client's request timeout, it doesn't affect watches.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Configures when a call must have completed by.
+    ///
+    /// The deadline is shared by the requests sent to every endpoint, including retries. A call
+    /// that has not completed by then fails with `Error::DeadlineExceeded`.
+    pub fn with_deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Configures a token that cancels a call when triggered.
+    ///
+    /// A cancelled call fails with `Error::Cancelled`, and any request in progress is dropped.
+    pub fn with_cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = Some(cancellation);
+        self
+    }
+
+    /// Adds a header to each HTTP request made for a call.
+    ///
+    /// NOTE: Calling this function multiple times with the same name adds each value.
+    pub fn with_header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.headers.append(name, value);
+        self
+    }
+
+    /// Returns how long each HTTP request may take.
+    pub(crate) fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    /// Returns when the whole call must have completed by.
+    pub(crate) fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    /// Returns the token that cancels the call.
+    pub(crate) fn cancellation(&self) -> Option<&CancellationToken> {
+        self.cancellation.as_ref()
+    }
+
+    /// Returns the headers added to each HTTP request.
+    pub(crate) fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+}
+
+/// A token used to cancel API calls.
+///
+/// Clones of a token share its state, so cancelling any of them cancels every call made with
+/// any of them.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+    /// The state shared by the token's clones.
+    inner: Arc<Inner>,
+}
+
+/// The state shared by clones of a `CancellationToken`.
+#[derive(Debug, Default)]
+struct Inner {
+    /// Whether the token has been cancelled.
+    cancelled: AtomicBool,
+    /// Wakes up the calls waiting for the token to be cancelled.
+    notify: Notify,
+}
+
+impl CancellationToken {
+    /// Creates a token that has not been cancelled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancels every call made with the token or its clones, including calls made later.
+    pub fn cancel(&self) {
+        self.inner.cancelled.store(true, Ordering::SeqCst);
+        self.inner.notify.notify_waiters();
+    }
+
+    /// Returns whether the token has been cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Waits until the token is cancelled.
+    pub(crate) async fn cancelled(&self) {
+        loop {
+            // Register for notifications before checking the flag, so that a cancellation in
+            // between is not missed.
+            let notified = self.inner.notify.notified();
+            if self.is_cancelled() {
+                return;
+            }
+            notified.await;
+        }
+    }
+}
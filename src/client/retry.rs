@@ -0,0 +1,143 @@
+//! Retrying requests that failed on every endpoint.
+
+use std::fmt::{Debug, Formatter, Result as FmtResult};
+use std::sync::Arc;
+use std::time::Duration;
+
+use rand::{thread_rng, Rng};
+
+use crate::error::Error;
+
+/// The etcd error code returned when raft fails internally, for example when a proposal times out.
+const RAFT_INTERNAL_ERROR: u64 = 300;
+
+/// The etcd error code returned when a request arrives during a leader election.
+const LEADER_ELECTION: u64 = 301;
+
+/// Configures how a `Client` retries requests that failed on every endpoint.
+///
+/// Each attempt tries every endpoint once. If all of them fail with errors the policy's classifier
+/// considers retryable, the client waits for an exponentially increasing, randomly jittered
+/// backoff and makes another attempt, until the maximum number of attempts is reached or the next
+/// attempt would start after the deadline.
+///
+/// Only idempotent or conditional requests are retried. Requests such as `kv::create_in_order`,
+/// which would have a different effect each time they are made, are always attempted once.
+#[derive(Clone)]
+pub struct RetryPolicy {
+    /// The maximum number of attempts, including the first one.
+    max_attempts: u32,
+    /// The backoff before the first retry.
+    initial_backoff: Duration,
+    /// The maximum backoff between two attempts.
+    max_backoff: Duration,
+    /// How long after the first attempt started retries may still be started.
+    deadline: Option<Duration>,
+    /// Decides whether an error is worth retrying.
+    classifier: Arc<dyn Fn(&Error) -> bool + Send + Sync>,
+}
+
+impl RetryPolicy {
+    /// Creates a new retry policy making at most `max_attempts` attempts, including the first.
+    ///
+    /// The backoff starts at 100 milliseconds and doubles after each attempt, up to 5 seconds.
+    /// There is no deadline, and errors are classified with `RetryPolicy::is_retryable_by_default`.
+    pub fn new(max_attempts: u32) -> Self {
+        RetryPolicy {
+            max_attempts,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(5),
+            deadline: None,
+            classifier: Arc::new(Self::is_retryable_by_default),
+        }
+    }
+
+    /// Configures the backoff before the first retry and the maximum backoff between two attempts.
+    ///
+    /// The backoff doubles after each attempt. The actual time waited is chosen at random between
+    /// half of the backoff and the full backoff, so that clients don't retry in lockstep.
+    pub fn with_backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.initial_backoff = initial;
+        self.max_backoff = max;
+        self
+    }
+
+    /// Configures how long after the first attempt started retries may still be started.
+    ///
+    /// Attempts that are in progress when the deadline passes are not interrupted.
+    pub fn with_deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Configures which errors are retried.
+    ///
+    /// An attempt is retried only if the classifier returns true for the error returned by every
+    /// endpoint.
+    pub fn with_classifier<C>(mut self, classifier: C) -> Self
+    where
+        C: Fn(&Error) -> bool + Send + Sync + 'static,
+    {
+        self.classifier = Arc::new(classifier);
+        self
+    }
+
+    /// Returns whether an error is retried by default.
+    ///
+    /// HTTP errors such as failed connections and timeouts, including those on `unix://`
+    /// endpoints and those returned by a custom `Transport`, 5xx responses, and etcd's raft
+    /// internal and leader election errors are retried. Other etcd errors, such as a failed compare or a
+    /// missing key, are not, as they would fail the same way again.
+    pub fn is_retryable_by_default(error: &Error) -> bool {
+        match *error {
+            Error::Http(_) | Error::Transport(_) | Error::UnixSocket(_) => true,
+            Error::Api(ref error) => {
+                error.error_code == RAFT_INTERNAL_ERROR || error.error_code == LEADER_ELECTION
+            }
+            Error::UnexpectedStatus(status) => status.is_server_error(),
+            _ => false,
+        }
+    }
+
+    /// Returns the maximum number of attempts, including the first one.
+    pub(crate) fn max_attempts(&self) -> u32 {
+        self.max_attempts
+    }
+
+    /// Returns how long after the first attempt started retries may still be started.
+    pub(crate) fn deadline(&self) -> Option<Duration> {
+        self.deadline
+    }
+
+    /// Returns whether a failed attempt that returned `errors` should be retried.
+    pub(crate) fn should_retry(&self, errors: &[Error]) -> bool {
+        errors.iter().all(|error| (self.classifier)(error))
+    }
+
+    /// Returns how long to wait after the given attempt, numbered from 1.
+    pub(crate) fn backoff(&self, attempt: u32) -> Duration {
+        jittered_backoff(self.initial_backoff, self.max_backoff, attempt)
+    }
+}
+
+/// Returns how long to wait after the given attempt, numbered from 1, for a backoff that starts
+/// at `initial` and doubles after each attempt, up to `max`.
+///
+/// The time is chosen at random between half of the backoff and the full backoff.
+pub(crate) fn jittered_backoff(initial: Duration, max: Duration, attempt: u32) -> Duration {
+    let exponent = attempt.saturating_sub(1).min(31);
+    let backoff = initial.saturating_mul(1 << exponent).min(max);
+    let half = backoff / 2;
+    half + thread_rng().gen_range(Duration::ZERO..=backoff - half)
+}
+
+impl Debug for RetryPolicy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("RetryPolicy")
+            .field("max_attempts", &self.max_attempts)
+            .field("initial_backoff", &self.initial_backoff)
+            .field("max_backoff", &self.max_backoff)
+            .field("deadline", &self.deadline)
+            .finish()
+    }
+}
@@ -0,0 +1,164 @@
+//! TLS material loaded from PEM files, and the background task that reloads it when the files
+//! change.
+
+use std::{
+    fs,
+    io::{Error as IoError, ErrorKind},
+    path::{Path, PathBuf},
+    sync::{PoisonError, RwLock, Weak},
+    time::{Duration, SystemTime},
+};
+
+use log::{error, info};
+use reqwest::{Certificate, Identity};
+
+use crate::error::Error;
+
+use super::HttpConfig;
+
+/// The PEM files a client loads its root certificates and client certificate from.
+#[derive(Clone, Debug)]
+pub(crate) struct TlsFiles {
+    /// The root certificates accepted by the client.
+    ca: PathBuf,
+    /// The client certificate.
+    cert: PathBuf,
+    /// The client certificate's private key.
+    key: PathBuf,
+}
+
+/// The root certificates and client certificate loaded from a client's `TlsFiles`.
+#[derive(Clone, Debug)]
+pub(crate) struct TlsMaterial {
+    /// The root certificates accepted by the client.
+    pub(crate) root_certificates: Vec<Certificate>,
+    /// The client certificate and its private key.
+    pub(crate) identity: Identity,
+}
+
+impl TlsFiles {
+    /// Creates the set of files to load TLS material from.
+    pub(crate) fn new(ca: PathBuf, cert: PathBuf, key: PathBuf) -> Self {
+        TlsFiles { ca, cert, key }
+    }
+
+    /// Loads the TLS material from the files, in the format expected by rustls if `rustls` is
+    /// true, or by native TLS otherwise.
+    pub(crate) fn load(&self, rustls: bool) -> Result<TlsMaterial, Error> {
+        let ca = read(&self.ca)?;
+        let cert = read(&self.cert)?;
+        let key = read(&self.key)?;
+
+        let root_certificates =
+            Certificate::from_pem_bundle(&ca).map_err(|error| invalid(&self.ca, error))?;
+
+        let identity = if rustls {
+            identity_from_pem(&cert, &key)
+        } else {
+            native_identity_from_pem(&cert, &key)
+        }
+        .map_err(|error| invalid(&self.cert, error))?;
+
+        Ok(TlsMaterial {
+            root_certificates,
+            identity,
+        })
+    }
+
+    /// Returns when each of the files was last modified, or `None` for files that cannot be read.
+    fn modified(&self) -> Vec<Option<SystemTime>> {
+        [&self.ca, &self.cert, &self.key]
+            .iter()
+            .map(|path| {
+                fs::metadata(path)
+                    .and_then(|metadata| metadata.modified())
+                    .ok()
+            })
+            .collect()
+    }
+}
+
+/// Reads a PEM file, including its path in the error if it cannot be read.
+fn read(path: &Path) -> Result<Vec<u8>, Error> {
+    fs::read(path).map_err(|error| {
+        Error::TlsFiles(IoError::new(
+            error.kind(),
+            format!("{}: {}", path.display(), error),
+        ))
+    })
+}
+
+/// Creates the error returned when a PEM file cannot be parsed.
+fn invalid(path: &Path, error: reqwest::Error) -> Error {
+    Error::TlsFiles(IoError::new(
+        ErrorKind::InvalidData,
+        format!("{}: {}", path.display(), error),
+    ))
+}
+
+#[cfg(feature = "rustls-tls")]
+/// Creates an identity for rustls from a certificate and a private key in PEM format.
+fn identity_from_pem(cert: &[u8], key: &[u8]) -> reqwest::Result<Identity> {
+    let mut pem = Vec::with_capacity(cert.len() + key.len() + 1);
+    pem.extend_from_slice(key);
+    pem.push(b'\n');
+    pem.extend_from_slice(cert);
+    Identity::from_pem(&pem)
+}
+
+#[cfg(not(feature = "rustls-tls"))]
+/// Never called, as rustls is only used with the `rustls-tls` feature.
+fn identity_from_pem(cert: &[u8], key: &[u8]) -> reqwest::Result<Identity> {
+    native_identity_from_pem(cert, key)
+}
+
+#[cfg(feature = "tls")]
+/// Creates an identity for native TLS from a certificate and a PKCS #8 private key in PEM format.
+fn native_identity_from_pem(cert: &[u8], key: &[u8]) -> reqwest::Result<Identity> {
+    Identity::from_pkcs8_pem(cert, key)
+}
+
+#[cfg(not(feature = "tls"))]
+/// Never called, as native TLS is only used with the `tls` feature.
+fn native_identity_from_pem(cert: &[u8], key: &[u8]) -> reqwest::Result<Identity> {
+    identity_from_pem(cert, key)
+}
+
+/// Checks `files` for changes every `interval`, replacing the HTTP client of a client with one
+/// that uses the new TLS material when they change, until the client and its clones are dropped.
+pub(crate) async fn reload_tls_files(
+    http_client: Weak<RwLock<reqwest::Client>>,
+    mut config: HttpConfig,
+    files: TlsFiles,
+    interval: Duration,
+) {
+    let mut modified = files.modified();
+    loop {
+        tokio::time::sleep(interval).await;
+
+        let http_client = match http_client.upgrade() {
+            Some(http_client) => http_client,
+            None => return,
+        };
+
+        let current = files.modified();
+        if current == modified {
+            continue;
+        }
+
+        // The files are checked again on the next tick if they cannot be loaded, as they may have
+        // been caught in the middle of being replaced.
+        let reloaded = files.load(config.use_rustls).and_then(|material| {
+            config.tls_material = Some(material);
+            Ok(config.build()?)
+        });
+        match reloaded {
+            Ok(reloaded) => {
+                *http_client.write().unwrap_or_else(PoisonError::into_inner) = reloaded;
+                modified = current;
+                info!("reloaded TLS files");
+            }
+            Err(error) => error!("failed to reload TLS files: {}", error),
+        }
+    }
+}
@@ -0,0 +1,78 @@
+//! Spans for the requests made to each endpoint, emitted when the `tracing` feature is enabled.
+//!
+//! Spans for whole API calls are created by the `tracing::instrument` attribute on each API
+//! function. Without the feature, the functions in this module do nothing.
+
+use std::future::Future;
+
+use http::{StatusCode, Uri};
+
+use crate::client::ClusterInfo;
+use crate::error::Error;
+
+/// Runs a request to `endpoint` in a span of its own, recording the etcd error code if it fails.
+pub(crate) async fn attempt<F, T>(endpoint: &Uri, request: F) -> Result<T, Error>
+where
+    F: Future<Output = Result<T, Error>>,
+{
+    #[cfg(feature = "tracing")]
+    {
+        use tracing::{field::Empty, Instrument};
+
+        let span = tracing::info_span!(
+            "etcd.attempt",
+            endpoint = %endpoint,
+            http.status = Empty,
+            etcd.error_code = Empty,
+            etcd.cluster_id = Empty,
+            etcd.etcd_index = Empty,
+            etcd.raft_index = Empty,
+            etcd.raft_term = Empty,
+            error = Empty,
+        );
+
+        let result = request.instrument(span.clone()).await;
+        match result {
+            Err(Error::Api(ref error)) => {
+                span.record("etcd.error_code", error.error_code);
+            }
+            Err(ref error) => {
+                span.record("error", tracing::field::display(error));
+            }
+            Ok(_) => {}
+        }
+        result
+    }
+
+    #[cfg(not(feature = "tracing"))]
+    {
+        let _ = endpoint;
+        request.await
+    }
+}
+
+/// Records the status and cluster information of a response on the current endpoint's span.
+pub(crate) fn record_response(status: StatusCode, cluster_info: &ClusterInfo) {
+    #[cfg(feature = "tracing")]
+    {
+        let span = tracing::Span::current();
+        span.record("http.status", status.as_u16());
+        if let Some(ref cluster_id) = cluster_info.cluster_id {
+            span.record("etcd.cluster_id", cluster_id.as_str());
+        }
+        if let Some(etcd_index) = cluster_info.etcd_index {
+            span.record("etcd.etcd_index", etcd_index);
+        }
+        if let Some(raft_index) = cluster_info.raft_index {
+            span.record("etcd.raft_index", raft_index);
+        }
+        if let Some(raft_term) = cluster_info.raft_term {
+            span.record("etcd.raft_term", raft_term);
+        }
+    }
+
+    #[cfg(not(feature = "tracing"))]
+    {
+        let _ = (status, cluster_info);
+    }
+}
@@ -0,0 +1,210 @@
+//! The transport that sends the HTTP requests a `Client` makes.
+
+use std::fmt::{Debug, Formatter, Result as FmtResult};
+use std::future::Future;
+use std::sync::{Arc, PoisonError, RwLock};
+use std::time::Duration;
+
+use bytes::Bytes;
+use futures::future::{BoxFuture, FutureExt};
+use http::header::HeaderMap;
+use http::{Method, StatusCode};
+use url::Url;
+
+use crate::error::Error;
+
+/// Sends the HTTP requests made by a `Client`.
+///
+/// By default, a client sends requests with `reqwest`, configured by the TLS, proxy and timeout
+/// settings of its `ClientBuilder`. A transport registered with `ClientBuilder::with_transport`
+/// replaces it, for example to use hyper with a custom connector, or to serve requests from an
+/// in-process fake in tests.
+///
+/// The client applies its own default headers, request timeouts, interceptors, retries and
+/// failover before a request reaches the transport, so a transport only needs to send each
+/// request once and return its response.
+pub trait Transport: Debug + Send + Sync {
+    /// Sends a request and returns its response once the response headers have been received.
+    ///
+    /// The body is received afterwards through the response's `ResponseBody`, so that the client
+    /// can tell how long etcd took to respond, even to watches whose body only arrives once the
+    /// watched key changes. Requests with a `timeout` should fail if the whole response, including
+    /// its body, has not been received in time. Errors that don't come from `reqwest` can be
+    /// returned as `Error::Transport`, and cause the client to try the next endpoint.
+    fn send(&self, request: HttpRequest) -> BoxFuture<'_, Result<HttpResponse, Error>>;
+}
+
+/// An HTTP request to an etcd endpoint, as passed to a `Transport`.
+#[derive(Clone, Debug)]
+pub struct HttpRequest {
+    /// The request's HTTP method.
+    pub method: Method,
+    /// The URL to send the request to.
+    ///
+    /// The URL of a `unix://` endpoint has the socket path hex-encoded as its host.
+    pub url: Url,
+    /// The request's headers.
+    pub headers: HeaderMap,
+    /// The request's body, if it has one.
+    pub body: Option<Bytes>,
+    /// How long the request may take, if it has a timeout.
+    pub timeout: Option<Duration>,
+}
+
+/// The response to an `HttpRequest`, as returned by a `Transport`.
+#[derive(Debug)]
+pub struct HttpResponse {
+    /// The response's HTTP status code.
+    pub status: StatusCode,
+    /// The response's headers.
+    pub headers: HeaderMap,
+    /// The response's body, which may still be being received.
+    pub body: ResponseBody,
+}
+
+/// The body of an `HttpResponse`.
+///
+/// A body is either already received, or received by a future once the response headers are.
+/// Bodies that are already received can be created from `Bytes`, a `Vec<u8>` or a `String`.
+pub struct ResponseBody {
+    /// The body, or the future receiving it.
+    inner: BodyInner,
+}
+
+/// The state of a `ResponseBody`.
+enum BodyInner {
+    /// A body that has been received.
+    Received(Bytes),
+    /// A body that is still being received.
+    Pending(BoxFuture<'static, Result<Bytes, Error>>),
+}
+
+impl ResponseBody {
+    /// Creates a body that is received by `future`.
+    ///
+    /// The future is only polled once the client reads the body.
+    pub fn from_future<F>(future: F) -> Self
+    where
+        F: Future<Output = Result<Bytes, Error>> + Send + 'static,
+    {
+        ResponseBody {
+            inner: BodyInner::Pending(future.boxed()),
+        }
+    }
+
+    /// Waits until the whole body has been received, and returns it.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the body could not be received.
+    pub async fn bytes(self) -> Result<Bytes, Error> {
+        match self.inner {
+            BodyInner::Received(bytes) => Ok(bytes),
+            BodyInner::Pending(future) => future.await,
+        }
+    }
+}
+
+impl From<Bytes> for ResponseBody {
+    fn from(bytes: Bytes) -> Self {
+        ResponseBody {
+            inner: BodyInner::Received(bytes),
+        }
+    }
+}
+
+impl From<Vec<u8>> for ResponseBody {
+    fn from(bytes: Vec<u8>) -> Self {
+        Bytes::from(bytes).into()
+    }
+}
+
+impl From<String> for ResponseBody {
+    fn from(string: String) -> Self {
+        Bytes::from(string).into()
+    }
+}
+
+impl Debug for ResponseBody {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self.inner {
+            BodyInner::Received(ref bytes) => f.debug_tuple("ResponseBody").field(bytes).finish(),
+            BodyInner::Pending(_) => f.write_str("ResponseBody(<pending>)"),
+        }
+    }
+}
+
+/// The default transport, which sends requests with `reqwest`, or over a Unix domain socket for
+/// `unix://` endpoints.
+#[derive(Debug)]
+pub(crate) struct ReqwestTransport {
+    /// The HTTP client, which is replaced when the client's TLS files are reloaded.
+    http_client: Arc<RwLock<reqwest::Client>>,
+    /// The client for `unix://` endpoints.
+    #[cfg(unix)]
+    unix_client: super::unix::UnixClient,
+}
+
+impl ReqwestTransport {
+    /// Creates a transport that sends requests with an HTTP client.
+    pub(crate) fn new(http_client: reqwest::Client) -> Self {
+        ReqwestTransport {
+            http_client: Arc::new(RwLock::new(http_client)),
+            #[cfg(unix)]
+            unix_client: super::unix::UnixClient::new(),
+        }
+    }
+
+    /// Returns the HTTP client, so that it can be replaced.
+    #[cfg(any(feature = "tls", feature = "rustls-tls"))]
+    pub(crate) fn http_client(&self) -> &Arc<RwLock<reqwest::Client>> {
+        &self.http_client
+    }
+
+    /// Sends a request with the HTTP client.
+    async fn send_http(&self, request: HttpRequest) -> Result<HttpResponse, Error> {
+        // The request is sent with the HTTP client at the time, even if the client's TLS files are
+        // reloaded before it completes.
+        let http_client = self
+            .http_client
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+
+        let builder = http_client
+            .request(request.method, request.url)
+            .headers(request.headers);
+        let builder = match request.body {
+            Some(body) => builder.body(body),
+            None => builder,
+        };
+        let builder = match request.timeout {
+            Some(timeout) => builder.timeout(timeout),
+            None => builder,
+        };
+
+        let response = builder.send().await?;
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = ResponseBody::from_future(async move { Ok(response.bytes().await?) });
+
+        Ok(HttpResponse {
+            status,
+            headers,
+            body,
+        })
+    }
+}
+
+impl Transport for ReqwestTransport {
+    fn send(&self, request: HttpRequest) -> BoxFuture<'_, Result<HttpResponse, Error>> {
+        #[cfg(unix)]
+        {
+            if request.url.scheme() == super::unix::SCHEME {
+                return self.unix_client.send(request).boxed();
+            }
+        }
+
+        self.send_http(request).boxed()
+    }
+}
@@ -0,0 +1,106 @@
+//! Support for endpoints that are Unix domain sockets, such as `unix:///var/run/etcd.sock`.
+//!
+//! `http::Uri` cannot represent a socket path, so a `unix://` endpoint is stored with the path
+//! hex-encoded as its host, as in `unix://2f7661722f72756e2f657463642e736f636b`. URLs built from
+//! such an endpoint keep the encoding, and the default transport sends requests to them over the
+//! socket rather than with the client's HTTP client.
+
+use http::uri::{InvalidUri, Uri};
+
+/// The scheme of endpoints that are Unix domain sockets.
+pub(crate) const SCHEME: &str = "unix";
+
+/// Parses an endpoint, encoding the socket path of a `unix://` endpoint as its host.
+///
+/// As with etcd's own `unix://` listeners, everything after `unix://` is the socket path.
+pub(crate) fn parse_endpoint(endpoint: &str) -> Result<Uri, InvalidUri> {
+    match endpoint.strip_prefix("unix://") {
+        Some(path) => {
+            let host: String = path.bytes().map(|byte| format!("{:02x}", byte)).collect();
+            format!("{}://{}", SCHEME, host).parse()
+        }
+        None => endpoint.parse(),
+    }
+}
+
+#[cfg(unix)]
+pub(crate) use self::transport::UnixClient;
+
+#[cfg(unix)]
+mod transport {
+    use std::future::Future;
+    use std::io::{Error as IoError, ErrorKind};
+
+    use hyperlocal::UnixConnector;
+
+    use crate::client::{HttpRequest, HttpResponse, ResponseBody};
+    use crate::error::Error;
+
+    /// Sends requests to endpoints that are Unix domain sockets.
+    #[derive(Clone, Debug)]
+    pub(crate) struct UnixClient {
+        /// The HTTP client that connects to sockets.
+        client: hyper::Client<UnixConnector>,
+    }
+
+    impl UnixClient {
+        /// Creates a client for `unix://` endpoints.
+        pub(crate) fn new() -> Self {
+            UnixClient {
+                client: hyper::Client::builder().build(UnixConnector),
+            }
+        }
+
+        /// Sends a request to a `unix://` endpoint.
+        pub(crate) async fn send(&self, request: HttpRequest) -> Result<HttpResponse, Error> {
+            let uri: hyper::Uri = request.url.as_str().parse().map_err(invalid_input)?;
+            let body = match request.body {
+                Some(body) => hyper::Body::from(body),
+                None => hyper::Body::empty(),
+            };
+
+            let mut hyper_request = hyper::Request::builder()
+                .method(request.method)
+                .uri(uri)
+                .body(body)
+                .map_err(invalid_input)?;
+            *hyper_request.headers_mut() = request.headers;
+
+            // The timeout covers receiving the body as well as the response headers.
+            let deadline = request
+                .timeout
+                .map(|timeout| tokio::time::Instant::now() + timeout);
+
+            let response = within(deadline, self.client.request(hyper_request)).await?;
+            let (parts, body) = response.into_parts();
+            let body = ResponseBody::from_future(within(deadline, hyper::body::to_bytes(body)));
+
+            Ok(HttpResponse {
+                status: parts.status,
+                headers: parts.headers,
+                body,
+            })
+        }
+    }
+
+    /// Waits for a step of a request to a socket, failing if `deadline` passes first.
+    async fn within<F, T>(deadline: Option<tokio::time::Instant>, future: F) -> Result<T, Error>
+    where
+        F: Future<Output = Result<T, hyper::Error>>,
+    {
+        let result = match deadline {
+            Some(deadline) => tokio::time::timeout_at(deadline, future)
+                .await
+                .map_err(|_| {
+                    Error::UnixSocket(IoError::new(ErrorKind::TimedOut, "request timed out"))
+                })?,
+            None => future.await,
+        };
+        result.map_err(|error| Error::UnixSocket(IoError::new(ErrorKind::Other, error)))
+    }
+
+    /// Creates the error returned when a request cannot be converted for a socket.
+    fn invalid_input(error: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> Error {
+        Error::UnixSocket(IoError::new(ErrorKind::InvalidInput, error))
+    }
+}
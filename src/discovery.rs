@@ -0,0 +1,319 @@
+//! Service registration and discovery.
+//!
+//! Each instance of a service registers itself as a key under the service's directory, named by
+//! its instance ID, with JSON-encoded metadata as the value. The key has a TTL and is refreshed by
+//! a heartbeat for as long as the instance is registered, so instances that die are removed by
+//! etcd once their key expires.
+
+use std::collections::HashMap;
+use std::marker::PhantomData;
+
+use futures::stream::{self, BoxStream, StreamExt};
+use log::error;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tokio::task::JoinHandle;
+
+use crate::client::Client;
+use crate::error::Error;
+use crate::kv::{self, watch_events, Action, Node, WatchError, WatchEvent, WatchOptions};
+use crate::recipes::{delete_in_background, is_key_not_found, refresh_interval, KeepAlive};
+
+/// A registered instance of a service, kept alive by a heartbeat until shut down or dropped.
+#[derive(Debug)]
+pub struct Registration {
+    /// The client used to make API calls.
+    client: Client,
+    /// The key representing the instance.
+    key: String,
+    /// The heartbeat task refreshing the key.
+    heartbeat: JoinHandle<()>,
+    /// Whether the instance has already been deregistered.
+    deregistered: bool,
+}
+
+/// The instances of a service at the time of discovery, along with all changes since.
+pub struct Discovery<M> {
+    /// The instances of the service at the time of discovery.
+    pub instances: Vec<Instance<M>>,
+    /// Additions and removals of instances since the time of discovery.
+    ///
+    /// The stream never ends on its own, and failed requests are yielded as errors without
+    /// ending it.
+    pub changes: BoxStream<'static, Result<DiscoveryEvent<M>, WatchError>>,
+}
+
+/// An instance of a service.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Instance<M> {
+    /// The unique identifier of the instance.
+    pub id: String,
+    /// The metadata the instance was registered with.
+    pub metadata: M,
+}
+
+/// A change to the instances of a service.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum DiscoveryEvent<M> {
+    /// An instance was registered, or re-registered with different metadata.
+    Added(Instance<M>),
+    /// The instance with the given ID was deregistered or expired.
+    Removed(String),
+}
+
+/// Registers an instance of a service.
+///
+/// The instance stays registered until `Registration::shutdown` is called or the `Registration`
+/// is dropped. If the heartbeat finds that the instance's key has expired, for example after a
+/// network partition, the instance is registered again.
+///
+/// # Parameters
+///
+/// * client: A `Client` to use to make the API calls.
+/// * service_dir: The directory holding the instances of the service.
+/// * instance_id: The unique identifier of the instance.
+/// * metadata: Information about the instance, such as its address, stored as JSON.
+/// * ttl: The TTL of the instance's key, in seconds. The key is refreshed every third of the TTL.
+///
+/// # Errors
+///
+/// Fails if `ttl` is zero, if the metadata cannot be serialized, or if the key cannot be set.
+pub async fn register<K, I, M>(
+    client: &Client,
+    service_dir: K,
+    instance_id: I,
+    metadata: &M,
+    ttl: u64,
+) -> Result<Registration, Vec<Error>>
+where
+    K: AsRef<str>,
+    I: AsRef<str>,
+    M: Serialize,
+{
+    if ttl == 0 {
+        return Err(vec![Error::InvalidTtl]);
+    }
+
+    let key = format!(
+        "{}/{}",
+        service_dir.as_ref().trim_end_matches('/'),
+        instance_id.as_ref()
+    );
+    let value = serde_json::to_string(metadata).map_err(|e| vec![e.into()])?;
+
+    kv::set(client, &key, &value, Some(ttl)).await?;
+    let heartbeat = tokio::spawn(heartbeat(client.clone(), key.clone(), value, ttl));
+
+    Ok(Registration {
+        client: client.clone(),
+        key,
+        heartbeat,
+        deregistered: false,
+    })
+}
+
+/// Discovers the instances of a service.
+///
+/// Instances whose metadata cannot be deserialized as `M` are skipped.
+///
+/// # Parameters
+///
+/// * client: A `Client` to use to make the API calls.
+/// * service_dir: The directory holding the instances of the service.
+///
+/// # Errors
+///
+/// Fails if the service directory cannot be listed.
+pub async fn discover<K, M>(client: &Client, service_dir: K) -> Result<Discovery<M>, WatchError>
+where
+    K: AsRef<str>,
+    M: DeserializeOwned + Send + 'static,
+{
+    let dir = service_dir.as_ref().trim_end_matches('/').to_owned();
+    let options = WatchOptions {
+        recursive: true,
+        ..Default::default()
+    };
+    let mut events = Box::pin(watch_events(client, &dir, options, true));
+    let mut state = DiscoveryState {
+        dir,
+        instances: HashMap::new(),
+        metadata: PhantomData,
+    };
+
+    let instances = match events.next().await {
+        Some(Ok((event, _))) => state
+            .apply(event)
+            .into_iter()
+            .filter_map(|event| match event {
+                DiscoveryEvent::Added(instance) => Some(instance),
+                DiscoveryEvent::Removed(_) => None,
+            })
+            .collect(),
+        Some(Err(error)) => return Err(error),
+        None => unreachable!("invariant: watch streams never end"),
+    };
+
+    let changes = events
+        .flat_map(move |result| {
+            let items: Vec<_> = match result {
+                Ok((event, _)) => state.apply(event).into_iter().map(Ok).collect(),
+                Err(error) => vec![Err(error)],
+            };
+            stream::iter(items)
+        })
+        .boxed();
+
+    Ok(Discovery { instances, changes })
+}
+
+impl Registration {
+    /// Returns the key representing the instance.
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Deregisters the instance, waiting for its key to be deleted.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the key could not be deleted. The instance will still be removed once the key
+    /// expires.
+    pub async fn shutdown(mut self) -> Result<(), Vec<Error>> {
+        self.deregistered = true;
+        self.heartbeat.abort();
+        match kv::delete(&self.client, &self.key, false).await {
+            Ok(_) => Ok(()),
+            Err(ref errors) if is_key_not_found(errors) => Ok(()),
+            Err(errors) => Err(errors),
+        }
+    }
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        self.heartbeat.abort();
+        if !self.deregistered {
+            delete_in_background(&self.client, &self.key);
+        }
+    }
+}
+
+impl<M> std::fmt::Debug for Discovery<M>
+where
+    M: std::fmt::Debug,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Discovery")
+            .field("instances", &self.instances)
+            .finish()
+    }
+}
+
+/// Keeps an instance's key alive, setting it again whenever it has been lost.
+async fn heartbeat(client: Client, key: String, value: String, ttl: u64) {
+    loop {
+        KeepAlive::spawn(&client, &key, ttl, None).lost().await;
+
+        while let Err(errors) = kv::set(&client, &key, &value, Some(ttl)).await {
+            error!("failed to register {} again: {:?}", key, errors);
+            tokio::time::sleep(refresh_interval(ttl)).await;
+        }
+    }
+}
+
+/// Tracks the known instances of a service to turn watch events into discovery events.
+struct DiscoveryState<M> {
+    /// The directory holding the instances of the service.
+    dir: String,
+    /// The raw metadata of each known instance, keyed by instance ID.
+    instances: HashMap<String, String>,
+    /// The type instance metadata is deserialized as.
+    metadata: PhantomData<fn() -> M>,
+}
+
+impl<M> DiscoveryState<M>
+where
+    M: DeserializeOwned,
+{
+    /// Applies a watch event for the service directory, returning the resulting changes.
+    fn apply(&mut self, event: WatchEvent) -> Vec<DiscoveryEvent<M>> {
+        match event {
+            WatchEvent::Change(kvi) => match kvi.action {
+                Action::Create | Action::Set | Action::Update | Action::CompareAndSwap => {
+                    self.add(kvi.node).into_iter().collect()
+                }
+                Action::Delete | Action::Expire | Action::CompareAndDelete => {
+                    let removed = match self.instance_id(&kvi.node) {
+                        Some(id) => vec![id.to_owned()],
+                        // The service directory itself was removed.
+                        None if kvi.node.key.as_deref() == Some(self.dir.as_str()) => {
+                            self.instances.keys().cloned().collect()
+                        }
+                        None => Vec::new(),
+                    };
+                    removed
+                        .into_iter()
+                        .filter(|id| self.instances.remove(id).is_some())
+                        .map(DiscoveryEvent::Removed)
+                        .collect()
+                }
+                Action::Get => Vec::new(),
+            },
+            WatchEvent::Resync(node) => {
+                let nodes = node.nodes.unwrap_or_default();
+                let present: Vec<String> = nodes
+                    .iter()
+                    .filter_map(|node| self.instance_id(node))
+                    .map(str::to_owned)
+                    .collect();
+                let removed: Vec<String> = self
+                    .instances
+                    .keys()
+                    .filter(|id| !present.contains(id))
+                    .cloned()
+                    .collect();
+
+                let mut events = Vec::new();
+                for id in removed {
+                    self.instances.remove(&id);
+                    events.push(DiscoveryEvent::Removed(id));
+                }
+                events.extend(nodes.into_iter().filter_map(|node| self.add(node)));
+                events
+            }
+        }
+    }
+
+    /// Records an instance, returning an event if it is new or its metadata changed.
+    fn add(&mut self, node: Node) -> Option<DiscoveryEvent<M>> {
+        let id = self.instance_id(&node)?.to_owned();
+        let value = node.value?;
+
+        if self.instances.get(&id) == Some(&value) {
+            return None;
+        }
+
+        match serde_json::from_str(&value) {
+            Ok(metadata) => {
+                self.instances.insert(id.clone(), value);
+                Some(DiscoveryEvent::Added(Instance { id, metadata }))
+            }
+            Err(e) => {
+                error!("failed to deserialize metadata of instance {}: {}", id, e);
+                None
+            }
+        }
+    }
+
+    /// Returns the instance ID of a node directly under the service directory.
+    fn instance_id<'a>(&self, node: &'a Node) -> Option<&'a str> {
+        let key = node.key.as_deref()?;
+        let id = key.strip_prefix(self.dir.as_str())?.strip_prefix('/')?;
+        if id.is_empty() || id.contains('/') {
+            None
+        } else {
+            Some(id)
+        }
+    }
+}
@@ -0,0 +1,457 @@
+//! Discovery of cluster member endpoints through DNS SRV records.
+//!
+//! etcd clusters can publish their client endpoints as `_etcd-client-ssl._tcp.<domain>` records
+//! for members served over HTTPS and `_etcd-client._tcp.<domain>` records for members served over
+//! HTTP. `ClientBuilder::from_dns_srv` looks up both and uses every record found as an endpoint.
+//!
+//! Lookups are made through an `SrvResolver`. The built-in `DnsResolver` sends queries over UDP,
+//! retrying over TCP when a response is truncated, to a list of name servers in turn. Custom
+//! implementations can be used to look up records some other way.
+
+use std::fmt::Debug;
+use std::fs;
+use std::io::{Error as IoError, ErrorKind};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+use http::Uri;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpStream, UdpSocket};
+
+use crate::error::Error;
+
+/// The SRV service name for cluster members serving their client API over HTTPS.
+const CLIENT_SSL_SERVICE: &str = "_etcd-client-ssl._tcp";
+
+/// The SRV service name for cluster members serving their client API over HTTP.
+const CLIENT_SERVICE: &str = "_etcd-client._tcp";
+
+/// The DNS record type for SRV records.
+const TYPE_SRV: u16 = 33;
+
+/// The DNS class for internet records.
+const CLASS_IN: u16 = 1;
+
+/// The DNS header flag set on responses that were truncated to fit in a UDP message.
+const FLAG_TRUNCATED: u16 = 0x0200;
+
+/// The DNS response code for a name that does not exist.
+const RCODE_NXDOMAIN: u16 = 3;
+
+/// The largest DNS message sent over UDP without extensions.
+const MAX_UDP_MESSAGE_SIZE: usize = 512;
+
+/// How long `DnsResolver` waits for a response, by default.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A DNS SRV record.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct SrvRecord {
+    /// The priority of the target host. Lower values are preferred.
+    pub priority: u16,
+    /// The relative weight of the target host among records with the same priority.
+    pub weight: u16,
+    /// The port the service is available on.
+    pub port: u16,
+    /// The host name of the target host, without a trailing dot.
+    pub target: String,
+}
+
+/// Looks up DNS SRV records.
+pub trait SrvResolver: Debug + Send + Sync {
+    /// Returns the SRV records for a name.
+    ///
+    /// Names ending with a dot are fully qualified. Resolvers may look up other names relative to
+    /// a list of search domains.
+    ///
+    /// Names without any SRV records, including names that do not exist, should resolve to an
+    /// empty list rather than an error.
+    fn resolve_srv<'a>(&'a self, name: &'a str) -> BoxFuture<'a, Result<Vec<SrvRecord>, IoError>>;
+}
+
+/// An `SrvResolver` that queries a list of name servers in turn.
+///
+/// Queries are sent over UDP, and sent again over TCP if the response was truncated. A name server
+/// that fails to respond, or responds with an error other than a missing name, is skipped in favor
+/// of the next one.
+///
+/// Names that are not fully qualified are looked up as given first, then relative to each search
+/// domain in turn, until one of them has SRV records.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct DnsResolver {
+    /// The addresses of the name servers to query, in order.
+    name_servers: Vec<SocketAddr>,
+    /// The domains that names which are not fully qualified are looked up relative to.
+    search_domains: Vec<String>,
+    /// How long to wait for a response from each name server.
+    timeout: Duration,
+}
+
+impl DnsResolver {
+    /// Creates a resolver that queries the given name server.
+    pub fn new(name_server: SocketAddr) -> Self {
+        DnsResolver {
+            name_servers: vec![name_server],
+            search_domains: Vec::new(),
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+
+    /// Creates a resolver that uses the name servers and search domains listed in
+    /// `/etc/resolv.conf`.
+    ///
+    /// # Errors
+    ///
+    /// Fails if `/etc/resolv.conf` cannot be read or does not list a valid name server.
+    pub fn system() -> Result<Self, IoError> {
+        let config = fs::read_to_string("/etc/resolv.conf")?;
+        let mut name_servers = Vec::new();
+        let mut search_domains = Vec::new();
+
+        for line in config.lines() {
+            let mut words = line.split_whitespace();
+            match words.next() {
+                Some("nameserver") => {
+                    if let Some(Ok(ip)) = words.next().map(str::parse::<IpAddr>) {
+                        name_servers.push(SocketAddr::new(ip, 53));
+                    }
+                }
+                // As with the system resolver, the last `domain` or `search` line wins.
+                Some("domain") | Some("search") => {
+                    search_domains = words.map(str::to_owned).collect();
+                }
+                _ => {}
+            }
+        }
+
+        if name_servers.is_empty() {
+            return Err(IoError::new(
+                ErrorKind::NotFound,
+                "no name server listed in /etc/resolv.conf",
+            ));
+        }
+
+        Ok(DnsResolver {
+            name_servers,
+            search_domains,
+            timeout: DEFAULT_TIMEOUT,
+        })
+    }
+
+    /// Adds a name server to query when the ones before it fail.
+    pub fn with_name_server(mut self, name_server: SocketAddr) -> Self {
+        self.name_servers.push(name_server);
+        self
+    }
+
+    /// Configures the domains that names which are not fully qualified are looked up relative to.
+    ///
+    /// There are no search domains by default.
+    pub fn with_search_domains<I, S>(mut self, search_domains: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.search_domains = search_domains.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Configures how long to wait for a response from each name server.
+    ///
+    /// The default is 5 seconds.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Looks up the SRV records of a single name, trying each name server in turn.
+    async fn lookup(&self, name: &str) -> Result<Vec<SrvRecord>, IoError> {
+        let mut last_error = None;
+
+        for &name_server in &self.name_servers {
+            let error = match tokio::time::timeout(self.timeout, query(name_server, name)).await {
+                Ok(Ok(records)) => return Ok(records),
+                Ok(Err(error)) => error,
+                Err(_) => IoError::new(
+                    ErrorKind::TimedOut,
+                    format!("timed out resolving {} with {}", name, name_server),
+                ),
+            };
+            last_error = Some(error);
+        }
+
+        Err(last_error
+            .unwrap_or_else(|| IoError::new(ErrorKind::NotFound, "no name server to query")))
+    }
+}
+
+impl SrvResolver for DnsResolver {
+    fn resolve_srv<'a>(&'a self, name: &'a str) -> BoxFuture<'a, Result<Vec<SrvRecord>, IoError>> {
+        Box::pin(async move {
+            if name.ends_with('.') {
+                return self.lookup(name).await;
+            }
+
+            let mut records = self.lookup(name).await?;
+            for domain in &self.search_domains {
+                if !records.is_empty() {
+                    break;
+                }
+                let relative = format!("{}.{}", name, domain.trim_end_matches('.'));
+                records = self.lookup(&relative).await?;
+            }
+
+            Ok(records)
+        })
+    }
+}
+
+/// Sends a query for the SRV records of `name` to a name server and parses the response.
+///
+/// The query is sent over UDP, and sent again over TCP if the response was truncated.
+async fn query(name_server: SocketAddr, name: &str) -> Result<Vec<SrvRecord>, IoError> {
+    let local_address: IpAddr = match name_server {
+        SocketAddr::V4(_) => Ipv4Addr::UNSPECIFIED.into(),
+        SocketAddr::V6(_) => Ipv6Addr::UNSPECIFIED.into(),
+    };
+    let socket = UdpSocket::bind(SocketAddr::new(local_address, 0)).await?;
+    socket.connect(name_server).await?;
+
+    let id = rand::random();
+    let query = encode_query(id, name)?;
+    socket.send(&query).await?;
+
+    let mut buffer = [0; MAX_UDP_MESSAGE_SIZE];
+    loop {
+        let length = socket.recv(&mut buffer).await?;
+        // Ignore stray responses to earlier queries.
+        match decode_response(id, &buffer[..length])? {
+            Some(Response::Records(records)) => return Ok(records),
+            Some(Response::Truncated) => return query_tcp(name_server, id, &query).await,
+            None => {}
+        }
+    }
+}
+
+/// Sends an encoded query with the given ID to a name server over TCP and parses the response.
+async fn query_tcp(
+    name_server: SocketAddr,
+    id: u16,
+    query: &[u8],
+) -> Result<Vec<SrvRecord>, IoError> {
+    let mut stream = TcpStream::connect(name_server).await?;
+
+    // Messages sent over TCP are prefixed with their length.
+    let mut message = Vec::with_capacity(2 + query.len());
+    message.extend_from_slice(&(query.len() as u16).to_be_bytes());
+    message.extend_from_slice(query);
+    stream.write_all(&message).await?;
+
+    let length = stream.read_u16().await? as usize;
+    let mut buffer = vec![0; length];
+    stream.read_exact(&mut buffer).await?;
+
+    match decode_response(id, &buffer)? {
+        Some(Response::Records(records)) => Ok(records),
+        Some(Response::Truncated) | None => Err(malformed()),
+    }
+}
+
+/// Looks up the client endpoints published for an etcd cluster under `domain`.
+///
+/// A `domain` ending with a dot is fully qualified, and is not looked up relative to the resolver's
+/// search domains.
+///
+/// HTTPS endpoints come first. Endpoints of each kind are ordered by priority, then by descending
+/// weight.
+pub(crate) async fn resolve_endpoints(
+    resolver: &dyn SrvResolver,
+    domain: &str,
+) -> Result<Vec<Uri>, Error> {
+    let mut endpoints = Vec::new();
+
+    for &(service, scheme) in &[(CLIENT_SSL_SERVICE, "https"), (CLIENT_SERVICE, "http")] {
+        let name = format!("{}.{}", service, domain);
+        let mut records = resolver.resolve_srv(&name).await.map_err(Error::Dns)?;
+        records.sort_by_key(|record| (record.priority, std::cmp::Reverse(record.weight)));
+
+        for record in records {
+            let target = record.target.trim_end_matches('.');
+            let endpoint = format!("{}://{}:{}", scheme, target, record.port).parse()?;
+            if !endpoints.contains(&endpoint) {
+                endpoints.push(endpoint);
+            }
+        }
+    }
+
+    if endpoints.is_empty() {
+        return Err(Error::NoEndpoints);
+    }
+
+    Ok(endpoints)
+}
+
+/// Encodes a recursive query for the SRV records of `name`.
+fn encode_query(id: u16, name: &str) -> Result<Vec<u8>, IoError> {
+    let mut message = Vec::with_capacity(MAX_UDP_MESSAGE_SIZE);
+    message.extend_from_slice(&id.to_be_bytes());
+    // Flags: a standard query with recursion desired.
+    message.extend_from_slice(&0x0100u16.to_be_bytes());
+    // One question, and no answer, authority or additional records.
+    message.extend_from_slice(&[0, 1, 0, 0, 0, 0, 0, 0]);
+
+    for label in name.trim_end_matches('.').split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return Err(IoError::new(
+                ErrorKind::InvalidInput,
+                format!("invalid DNS name: {}", name),
+            ));
+        }
+        message.push(label.len() as u8);
+        message.extend_from_slice(label.as_bytes());
+    }
+    message.push(0);
+
+    message.extend_from_slice(&TYPE_SRV.to_be_bytes());
+    message.extend_from_slice(&CLASS_IN.to_be_bytes());
+    Ok(message)
+}
+
+/// A decoded response to a query.
+enum Response {
+    /// The SRV records in a complete response.
+    Records(Vec<SrvRecord>),
+    /// The response did not fit in a UDP message, and the query must be sent over TCP.
+    Truncated,
+}
+
+/// Decodes a response to the query with the given ID.
+///
+/// Returns `None` if the message is not a response to that query.
+fn decode_response(id: u16, message: &[u8]) -> Result<Option<Response>, IoError> {
+    let mut reader = Reader {
+        message,
+        position: 0,
+    };
+
+    let response_id = reader.u16()?;
+    let flags = reader.u16()?;
+    let is_response = flags & 0x8000 != 0;
+    if response_id != id || !is_response {
+        return Ok(None);
+    }
+
+    if flags & FLAG_TRUNCATED != 0 {
+        return Ok(Some(Response::Truncated));
+    }
+
+    match flags & 0x000f {
+        0 => {}
+        RCODE_NXDOMAIN => return Ok(Some(Response::Records(Vec::new()))),
+        rcode => {
+            return Err(IoError::new(
+                ErrorKind::Other,
+                format!("name server returned response code {}", rcode),
+            ))
+        }
+    }
+
+    let questions = reader.u16()?;
+    let answers = reader.u16()?;
+    reader.skip(4)?;
+
+    for _ in 0..questions {
+        reader.name()?;
+        reader.skip(4)?;
+    }
+
+    let mut records = Vec::new();
+    for _ in 0..answers {
+        reader.name()?;
+        let record_type = reader.u16()?;
+        let class = reader.u16()?;
+        reader.skip(4)?;
+        let length = reader.u16()? as usize;
+        let end = reader.position + length;
+
+        if record_type == TYPE_SRV && class == CLASS_IN {
+            records.push(SrvRecord {
+                priority: reader.u16()?,
+                weight: reader.u16()?,
+                port: reader.u16()?,
+                target: reader.name()?,
+            });
+        }
+
+        reader.position = end;
+    }
+
+    Ok(Some(Response::Records(records)))
+}
+
+/// Reads fields from a DNS message.
+struct Reader<'a> {
+    /// The entire message, which compressed names point into.
+    message: &'a [u8],
+    /// The position of the next field to read.
+    position: usize,
+}
+
+impl Reader<'_> {
+    /// Reads a big-endian 16-bit integer.
+    fn u16(&mut self) -> Result<u16, IoError> {
+        let bytes = self.bytes(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    /// Skips over `length` bytes.
+    fn skip(&mut self, length: usize) -> Result<(), IoError> {
+        self.bytes(length).map(|_| ())
+    }
+
+    /// Reads `length` bytes.
+    fn bytes(&mut self, length: usize) -> Result<&[u8], IoError> {
+        let bytes = self
+            .message
+            .get(self.position..self.position + length)
+            .ok_or_else(malformed)?;
+        self.position += length;
+        Ok(bytes)
+    }
+
+    /// Reads a possibly compressed domain name, without a trailing dot.
+    fn name(&mut self) -> Result<String, IoError> {
+        let mut labels = Vec::new();
+        let mut position = self.position;
+        let mut end = None;
+
+        // Bound the number of labels so that compression loops cannot hang.
+        for _ in 0..128 {
+            let length = *self.message.get(position).ok_or_else(malformed)? as usize;
+
+            if length & 0xc0 == 0xc0 {
+                let low = *self.message.get(position + 1).ok_or_else(malformed)? as usize;
+                end.get_or_insert(position + 2);
+                position = ((length & 0x3f) << 8) | low;
+            } else if length == 0 {
+                self.position = end.unwrap_or(position + 1);
+                return Ok(labels.join("."));
+            } else {
+                let label = self
+                    .message
+                    .get(position + 1..position + 1 + length)
+                    .ok_or_else(malformed)?;
+                labels.push(String::from_utf8_lossy(label).into_owned());
+                position += 1 + length;
+            }
+        }
+
+        Err(malformed())
+    }
+}
+
+/// Returns the error for a DNS message that cannot be parsed.
+fn malformed() -> IoError {
+    IoError::new(ErrorKind::InvalidData, "malformed DNS response")
+}
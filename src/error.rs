@@ -3,6 +3,7 @@
 use std::convert::From;
 use std::error::Error as StdError;
 use std::fmt::{Display, Error as FmtError, Formatter};
+use std::io::Error as IoError;
 
 use http::{uri::InvalidUri, StatusCode};
 use serde_derive::{Deserialize, Serialize};
@@ -10,6 +11,9 @@ use serde_json::Error as SerializationError;
 use tokio::time::error::Elapsed;
 use url::ParseError as UrlError;
 
+/// The etcd error code returned when a watch index is older than etcd's event history.
+const EVENT_INDEX_CLEARED: u64 = 401;
+
 /// An error returned by an etcd API endpoint.
 ///
 /// This is a logical error, as opposed to other types of errors that may occur when using this
@@ -44,11 +48,20 @@ impl StdError for ApiError {
 pub enum Error {
     /// An error returned by an etcd API endpoint.
     Api(ApiError),
+    /// An error returned when a call is cancelled through its `CancellationToken`.
+    Cancelled,
+    /// An error returned when a call does not complete before the deadline in its
+    /// `RequestOptions`.
+    DeadlineExceeded,
+    /// An error returned when cluster member endpoints cannot be discovered via DNS.
+    Dns(IoError),
     /// An error at the HTTP protocol layer.
     Http(reqwest::Error),
     /// An error returned when invalid conditions have been provided for a compare-and-delete or
     /// compare-and-swap operation.
     InvalidConditions,
+    /// An error returned when a key that must be kept alive is given a TTL of zero.
+    InvalidTtl,
     /// An error returned when an etcd cluster member's endpoint is not a valid URI.
     InvalidUri(InvalidUri),
     /// An error returned when the URL for a specific API endpoint cannot be generated.
@@ -57,25 +70,38 @@ pub enum Error {
     NoEndpoints,
     /// An error returned when attempting to deserializing invalid JSON.
     Serialization(SerializationError),
+    /// An error returned when TLS certificates or keys cannot be loaded from files.
+    TlsFiles(IoError),
+    /// An error returned by a custom `Transport` when a request fails.
+    Transport(Box<dyn StdError + Send + Sync>),
     /// An error returned when an unexpected HTTP status code is returned by the server.
     UnexpectedStatus(StatusCode),
+    /// An error returned when a request to an endpoint that is a Unix domain socket fails.
+    UnixSocket(IoError),
 }
 
 impl Display for Error {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), FmtError> {
         match *self {
             Error::Api(ref error) => write!(f, "{}", error),
+            Error::Cancelled => write!(f, "the call was cancelled"),
+            Error::DeadlineExceeded => write!(f, "the call did not complete before its deadline"),
+            Error::Dns(ref error) => write!(f, "{}", error),
             Error::Http(ref error) => write!(f, "{}", error),
-            ref error @ Error::InvalidConditions => write!(f, "{}", error.to_string()),
+            Error::InvalidConditions => write!(f, "current value or modified index is required"),
+            Error::InvalidTtl => write!(f, "the TTL must be at least one second"),
             Error::InvalidUri(ref error) => write!(f, "{}", error),
             Error::InvalidUrl(ref error) => write!(f, "{}", error),
-            ref error @ Error::NoEndpoints => write!(f, "{}", error.to_string()),
+            Error::NoEndpoints => write!(f, "at least one endpoint is required to create a Client"),
             Error::Serialization(ref error) => write!(f, "{}", error),
+            Error::TlsFiles(ref error) => write!(f, "{}", error),
+            Error::Transport(ref error) => write!(f, "{}", error),
             Error::UnexpectedStatus(ref status) => write!(
                 f,
                 "the etcd server returned an unexpected HTTP status code: {}",
                 status
             ),
+            Error::UnixSocket(ref error) => write!(f, "{}", error),
         }
     }
 }
@@ -84,13 +110,20 @@ impl StdError for Error {
     fn description(&self) -> &str {
         match *self {
             Error::Api(_) => "the etcd server returned an error",
+            Error::Cancelled => "the call was cancelled",
+            Error::DeadlineExceeded => "the call did not complete before its deadline",
+            Error::Dns(_) => "endpoints could not be discovered via DNS",
             Error::Http(_) => "an error occurred during the HTTP request",
             Error::InvalidConditions => "current value or modified index is required",
+            Error::InvalidTtl => "the TTL must be at least one second",
             Error::InvalidUri(_) => "a supplied endpoint could not be parsed as a URI",
             Error::InvalidUrl(_) => "a URL for the request could not be generated",
             Error::NoEndpoints => "at least one endpoint is required to create a Client",
             Error::Serialization(_) => "an error occurred deserializing JSON",
+            Error::TlsFiles(_) => "TLS certificates or keys could not be loaded from files",
+            Error::Transport(_) => "an error occurred sending the request",
             Error::UnexpectedStatus(_) => "the etcd server returned an unexpected HTTP status code",
+            Error::UnixSocket(_) => "an error occurred during the request to a Unix domain socket",
         }
     }
 }
@@ -128,6 +161,23 @@ pub enum WatchError {
     Timeout,
 }
 
+impl WatchError {
+    /// Returns whether the watch failed because the requested index has already been flushed out
+    /// of etcd's internal store of the most recent change events.
+    ///
+    /// In this case, changes have been missed and the watched node should be read again before
+    /// watching from the index it was read at.
+    pub fn is_event_index_cleared(&self) -> bool {
+        match *self {
+            WatchError::Other(ref errors) => errors.iter().any(|error| match *error {
+                Error::Api(ref error) => error.error_code == EVENT_INDEX_CLEARED,
+                _ => false,
+            }),
+            WatchError::Timeout => false,
+        }
+    }
+}
+
 impl From<Elapsed> for WatchError {
     fn from(_: Elapsed) -> Self {
         WatchError::Timeout
@@ -137,8 +187,11 @@ impl From<Elapsed> for WatchError {
 impl Display for WatchError {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), FmtError> {
         match *self {
-            WatchError::Timeout => write!(f, "{}", self.to_string()),
-            ref other => other.fmt(f),
+            WatchError::Other(ref errors) => match errors.first() {
+                Some(error) => write!(f, "{}", error),
+                None => write!(f, "the watch failed without an error"),
+            },
+            WatchError::Timeout => write!(f, "operation timed out"),
         }
     }
 }
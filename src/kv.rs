@@ -6,20 +6,33 @@
 
 use std::time::Duration;
 
-use http::{StatusCode, Uri};
+use futures::stream::{self, Stream, StreamExt};
+use http::{header::HeaderValue, Method, StatusCode, Uri};
 use serde_derive::{Deserialize, Serialize};
 use tokio::time::timeout;
 
+pub use self::reflector::{Reflector, Snapshot};
 pub use crate::error::WatchError;
 
-use crate::client::{parse_etcd_response, Client, Response};
+use crate::client::{jittered_backoff, parse_etcd_response, Client, RequestKind, Response};
 use crate::error::Error;
 use crate::options::{
     ComparisonConditions, DeleteOptions, GetOptions as InternalGetOptions, SetOptions,
 };
 
+mod reflector;
+
 type EtcdKeyValueResult<E = Vec<Error>> = Result<Response<KeyValueInfo>, E>;
 
+/// The etcd error code returned when a key does not exist.
+const KEY_NOT_FOUND: u64 = 100;
+
+/// The backoff `watch_stream` waits for before retrying after the first failed request.
+const WATCH_INITIAL_BACKOFF: Duration = Duration::from_millis(100);
+
+/// The maximum backoff `watch_stream` waits for before retrying after consecutive failed requests.
+const WATCH_MAX_BACKOFF: Duration = Duration::from_secs(5);
+
 /// Information about the result of a successful key-value API operation.
 #[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
 pub struct KeyValueInfo {
@@ -64,7 +77,7 @@ pub enum Action {
 }
 
 /// An etcd key or directory.
-#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[derive(Clone, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
 pub struct Node {
     /// The new value of the etcd creation index.
     #[serde(rename = "createdIndex")]
@@ -86,6 +99,19 @@ pub struct Node {
     pub value: Option<String>,
 }
 
+/// An event yielded by `kv::watch_stream`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum WatchEvent {
+    /// A change to the watched node.
+    Change(KeyValueInfo),
+    /// The watch fell too far behind and changes were missed, so the watched node was re-read.
+    ///
+    /// Contains the current state of the node, including all of its children. Any state built
+    /// from earlier events should be discarded and rebuilt from this node. If the node does not
+    /// exist, it will have no value, children, or indexes.
+    Resync(Node),
+}
+
 /// Options for customizing the behavior of `kv::get`.
 #[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
 pub struct GetOptions {
@@ -97,8 +123,16 @@ pub struct GetOptions {
     /// If true, the etcd node serving the response will synchronize with the quorum before
     /// returning the value.
     ///
-    /// This is slower but avoids possibly stale data from being returned.
+    /// This is slower but avoids possibly stale data from being returned. Reads are sent with
+    /// etcd's `quorum=true` parameter; without it, the member serving the request answers from
+    /// its local state, which may lag behind the leader.
     pub strong_consistency: bool,
+    /// If given, the request is also sent to the next endpoint whenever no response has arrived
+    /// within the duration, and the first successful response is returned.
+    ///
+    /// Setting this to around the 95th percentile of response times cuts the tail latency caused
+    /// by slow cluster members, at the cost of a few extra requests.
+    pub hedge_delay: Option<Duration>,
 }
 
 /// Options for customizing the behavior of `kv::watch`.
@@ -120,13 +154,17 @@ pub struct WatchOptions {
 /// * client: A `Client` to use to make the API call.
 /// * key: The name of the node to delete.
 /// * current_value: If given, the node must currently have this value for the operation to
-/// succeed.
+///   succeed.
 /// * current_modified_index: If given, the node must currently be at this modified index for the
-/// operation to succeed.
+///   operation to succeed.
 ///
 /// # Errors
 ///
 /// Fails if the conditions didn't match or if no conditions were given.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(name = "etcd.kv.compare_and_delete", skip_all, fields(key = key.as_ref()))
+)]
 pub async fn compare_and_delete<K>(
     client: &Client,
     key: K,
@@ -160,13 +198,17 @@ where
 /// * value: The new value for the node.
 /// * ttl: If given, the node will expire after this many seconds.
 /// * current_value: If given, the node must currently have this value for the operation to
-/// succeed.
+///   succeed.
 /// * current_modified_index: If given, the node must currently be at this modified index for the
-/// operation to succeed.
+///   operation to succeed.
 ///
 /// # Errors
 ///
 /// Fails if the conditions didn't match or if no conditions were given.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(name = "etcd.kv.compare_and_swap", skip_all, fields(key = key.as_ref()))
+)]
 pub async fn compare_and_swap<K, V>(
     client: &Client,
     key: K,
@@ -209,6 +251,10 @@ where
 /// # Errors
 ///
 /// Fails if the key already exists.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(name = "etcd.kv.create", skip_all, fields(key = key.as_ref()))
+)]
 pub async fn create<K, V>(client: &Client, key: K, value: V, ttl: Option<u64>) -> EtcdKeyValueResult
 where
     K: AsRef<str>,
@@ -240,6 +286,10 @@ where
 /// # Errors
 ///
 /// Fails if the key already exists.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(name = "etcd.kv.create_dir", skip_all, fields(key = key.as_ref()))
+)]
 pub async fn create_dir<K>(client: &Client, key: K, ttl: Option<u64>) -> EtcdKeyValueResult
 where
     K: AsRef<str>,
@@ -250,7 +300,7 @@ where
         SetOptions {
             dir: Some(true),
             prev_exist: Some(false),
-            ttl: ttl,
+            ttl,
             ..Default::default()
         },
     )
@@ -276,6 +326,10 @@ where
 /// # Errors
 ///
 /// Fails if the key already exists and is not a directory.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(name = "etcd.kv.create_in_order", skip_all, fields(key = key.as_ref()))
+)]
 pub async fn create_in_order<K, V>(
     client: &Client,
     key: K,
@@ -292,7 +346,7 @@ where
         key,
         SetOptions {
             create_in_order: true,
-            ttl: ttl,
+            ttl,
             value: Some(value),
             ..Default::default()
         },
@@ -307,11 +361,15 @@ where
 /// * client: A `Client` to use to make the API call.
 /// * key: The name of the node to delete.
 /// * recursive: If true, and the key is a directory, the directory and all child key-value
-/// pairs and directories will be deleted as well.
+///   pairs and directories will be deleted as well.
 ///
 /// # Errors
 ///
 /// Fails if the key is a directory and `recursive` is `false`.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(name = "etcd.kv.delete", skip_all, fields(key = key.as_ref()))
+)]
 pub async fn delete<K>(client: &Client, key: K, recursive: bool) -> EtcdKeyValueResult
 where
     K: AsRef<str>,
@@ -337,6 +395,10 @@ where
 /// # Errors
 ///
 /// Fails if the directory is not empty.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(name = "etcd.kv.delete_dir", skip_all, fields(key = key.as_ref()))
+)]
 pub async fn delete_dir<K>(client: &Client, key: K) -> EtcdKeyValueResult
 where
     K: AsRef<str>,
@@ -363,6 +425,10 @@ where
 /// # Errors
 ///
 /// Fails if the key doesn't exist.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(name = "etcd.kv.get", skip_all, fields(key = key.as_ref()))
+)]
 pub async fn get<K>(client: &Client, key: K, options: GetOptions) -> EtcdKeyValueResult
 where
     K: AsRef<str>,
@@ -376,6 +442,7 @@ where
             strong_consistency: options.strong_consistency,
             ..Default::default()
         },
+        options.hedge_delay,
     )
     .await
 }
@@ -394,6 +461,10 @@ where
 /// # Errors
 ///
 /// Fails if the node is a directory.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(name = "etcd.kv.set", skip_all, fields(key = key.as_ref()))
+)]
 pub async fn set<K, V>(client: &Client, key: K, value: V, ttl: Option<u64>) -> EtcdKeyValueResult
 where
     K: AsRef<str>,
@@ -423,6 +494,10 @@ where
 /// # Errors
 ///
 /// Fails if the node does not exist.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(name = "etcd.kv.refresh", skip_all, fields(key = key.as_ref()))
+)]
 pub async fn refresh<K>(client: &Client, key: K, ttl: u64) -> EtcdKeyValueResult
 where
     K: AsRef<str>,
@@ -453,6 +528,10 @@ where
 /// # Errors
 ///
 /// Fails if the node is an existing directory.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(name = "etcd.kv.set_dir", skip_all, fields(key = key.as_ref()))
+)]
 pub async fn set_dir<K>(client: &Client, key: K, ttl: Option<u64>) -> EtcdKeyValueResult
 where
     K: AsRef<str>,
@@ -481,6 +560,10 @@ where
 /// # Errors
 ///
 /// Fails if the key does not exist.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(name = "etcd.kv.update", skip_all, fields(key = key.as_ref()))
+)]
 pub async fn update<K, V>(client: &Client, key: K, value: V, ttl: Option<u64>) -> EtcdKeyValueResult
 where
     K: AsRef<str>,
@@ -514,6 +597,10 @@ where
 /// # Errors
 ///
 /// Fails if the node does not exist.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(name = "etcd.kv.update_dir", skip_all, fields(key = key.as_ref()))
+)]
 pub async fn update_dir<K>(client: &Client, key: K, ttl: Option<u64>) -> EtcdKeyValueResult
 where
     K: AsRef<str>,
@@ -548,6 +635,10 @@ where
 ///
 /// Fails if a timeout is specified and the duration lapses without a response from the etcd
 /// cluster.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(name = "etcd.kv.watch", skip_all, fields(key = key.as_ref()))
+)]
 pub async fn watch<K>(
     client: &Client,
     key: K,
@@ -565,7 +656,9 @@ where
             wait: true,
             ..Default::default()
         },
+        None,
     );
+    let _open_watch = client.open_watch();
 
     if let Some(duration) = options.timeout {
         match timeout(duration, fut).await {
@@ -577,6 +670,148 @@ where
     }
 }
 
+/// Watches a node for changes continuously, yielding each change as it takes place.
+///
+/// Unlike `watch`, which resolves after a single change, the returned stream re-issues the watch
+/// request after every change, tracking the next index to wait on itself so that no change is
+/// missed between requests. The stream never ends on its own; drop it to stop watching.
+///
+/// If the watch falls so far behind that etcd has already flushed the next index from its event
+/// history, the node is re-read with a recursive `get` and yielded as `WatchEvent::Resync`. The
+/// watch then resumes from the index the node was read at.
+///
+/// # Parameters
+///
+/// * client: A `Client` to use to make the API calls.
+/// * key: The name of the node to watch.
+/// * options: Options to customize the behavior of the operation. `options.index` is used as the
+///   starting index, and `options.timeout` applies to each individual request.
+///
+/// # Errors
+///
+/// Failed requests are yielded as errors without ending the stream. The next poll retries the
+/// watch from the same index, after a backoff that starts at 100 milliseconds and doubles with
+/// each consecutive failure, up to 5 seconds. Requests that time out after `options.timeout`
+/// without a change are retried immediately.
+pub fn watch_stream<K>(
+    client: &Client,
+    key: K,
+    options: WatchOptions,
+) -> impl Stream<Item = Result<WatchEvent, WatchError>>
+where
+    K: AsRef<str>,
+{
+    watch_events(client, key.as_ref(), options, false)
+        .map(|result| result.map(|(event, _index)| event))
+}
+
+/// Drives `watch_stream`, additionally yielding the etcd index each event is current as of.
+///
+/// If `needs_resync` is true, the node is read before watching starts, so the first event is
+/// always `WatchEvent::Resync`.
+pub(crate) fn watch_events(
+    client: &Client,
+    key: &str,
+    options: WatchOptions,
+    needs_resync: bool,
+) -> impl Stream<Item = Result<(WatchEvent, u64), WatchError>> {
+    let state = WatchStreamState {
+        client: client.clone(),
+        key: key.to_owned(),
+        options,
+        needs_resync,
+        failures: 0,
+    };
+
+    stream::unfold(state, |mut state| async move {
+        if state.failures > 0 {
+            let backoff =
+                jittered_backoff(WATCH_INITIAL_BACKOFF, WATCH_MAX_BACKOFF, state.failures);
+            tokio::time::sleep(backoff).await;
+        }
+
+        let item = next_watch_event(&mut state).await;
+        match item {
+            Ok(_) | Err(WatchError::Timeout) => state.failures = 0,
+            Err(_) => state.failures = state.failures.saturating_add(1),
+        }
+        Some((item, state))
+    })
+}
+
+/// Makes the next request for `watch_stream`, re-reading the node if the watch fell behind.
+async fn next_watch_event(state: &mut WatchStreamState) -> Result<(WatchEvent, u64), WatchError> {
+    if !state.needs_resync {
+        match watch(&state.client, &state.key, state.options).await {
+            Ok(response) => {
+                let index = response
+                    .data
+                    .node
+                    .modified_index
+                    .or(response.cluster_info.etcd_index)
+                    .unwrap_or_default();
+                state.options.index = Some(index + 1);
+                return Ok((WatchEvent::Change(response.data), index));
+            }
+            Err(ref error) if error.is_event_index_cleared() => state.needs_resync = true,
+            Err(error) => return Err(error),
+        }
+    }
+
+    resync(state).await
+}
+
+/// The state carried between the individual requests made by `watch_stream`.
+struct WatchStreamState {
+    /// The client used to make the API calls.
+    client: Client,
+    /// The name of the node being watched.
+    key: String,
+    /// The options for the next request, including the next index to wait on.
+    options: WatchOptions,
+    /// Whether the node must be re-read before watching can resume.
+    needs_resync: bool,
+    /// The number of consecutive requests that failed, which the backoff before the next request
+    /// grows with.
+    failures: u32,
+}
+
+/// Re-reads the node watched by `watch_stream` and moves the next index to wait on past it.
+async fn resync(state: &mut WatchStreamState) -> Result<(WatchEvent, u64), WatchError> {
+    let options = GetOptions {
+        recursive: true,
+        ..Default::default()
+    };
+
+    let (node, etcd_index) = match get(&state.client, &state.key, options).await {
+        Ok(response) => (response.data.node, response.cluster_info.etcd_index),
+        Err(errors) => match key_not_found_index(&errors) {
+            Some(index) => {
+                let node = Node {
+                    key: Some(state.key.clone()),
+                    ..Default::default()
+                };
+                (node, Some(index))
+            }
+            None => return Err(WatchError::Other(errors)),
+        },
+    };
+
+    let index = etcd_index.unwrap_or_default();
+    state.options.index = Some(index + 1);
+    state.needs_resync = false;
+
+    Ok((WatchEvent::Resync(node), index))
+}
+
+/// Returns the etcd index at which a key was found to not exist, if that is why a request failed.
+pub(crate) fn key_not_found_index(errors: &[Error]) -> Option<u64> {
+    errors.iter().find_map(|error| match *error {
+        Error::Api(ref error) if error.error_code == KEY_NOT_FOUND => Some(error.index),
+        _ => None,
+    })
+}
+
 /// Handles all delete operations.
 async fn raw_delete<K>(client: &Client, key: K, options: DeleteOptions<'_>) -> EtcdKeyValueResult
 where
@@ -585,35 +820,48 @@ where
     let key = key.as_ref();
     let query_params = options.into_query_params().map_err(|e| vec![e])?;
 
-    client
-        .first_ok(move |client, endpoint| {
+    let result = client
+        .first_ok_with(RequestKind::Write, move |client, endpoint| {
             let url = build_url(endpoint, key, Some(&query_params));
             async move {
-                let response = client.http_client().delete(url).send().await?;
+                let response = client.http_request(Method::DELETE, url).send().await?;
                 parse_etcd_response(response, |s| s == StatusCode::OK).await
             }
         })
-        .await
+        .await;
+
+    observe_cluster_info(client, result)
 }
 
 /// Handles all get operations.
-async fn raw_get<K>(client: &Client, key: K, options: InternalGetOptions) -> EtcdKeyValueResult
+///
+/// If a hedge delay is given, the request is sent to more than one endpoint at a time.
+async fn raw_get<K>(
+    client: &Client,
+    key: K,
+    options: InternalGetOptions,
+    hedge_delay: Option<Duration>,
+) -> EtcdKeyValueResult
 where
     K: AsRef<str>,
 {
     let wait = options.wait;
     let query_params = options.into_query_params();
     let key = key.as_ref();
+    let kind = match hedge_delay {
+        Some(hedge_delay) => RequestKind::Hedged(hedge_delay),
+        None => RequestKind::Idempotent,
+    };
 
-    client
-        .first_ok(move |client, endpoint| {
+    let result = client
+        .first_ok_with(kind, move |client, endpoint| {
             let url = build_url(endpoint, key, Some(&query_params));
             async move {
-                let request = client.http_client().get(url);
+                let request = client.http_request(Method::GET, url);
                 let request = if wait {
-                    // Since `reqwest` doesn't let us specify a timeout, we'll set an arbitrary
-                    // large amount of requests.
-                    request.timeout(Duration::from_secs(60 * 60 * 24))
+                    // A watch waits for as long as it takes the key to change, so the client's
+                    // request timeout doesn't apply. `watch` applies its own timeout instead.
+                    request.without_timeout()
                 } else {
                     request
                 };
@@ -621,7 +869,9 @@ where
                 parse_etcd_response(response, |s| s == StatusCode::OK).await
             }
         })
-        .await
+        .await;
+
+    observe_cluster_info(client, result)
 }
 
 /// Handles all set operations.
@@ -633,20 +883,27 @@ where
     let create_in_order = options.create_in_order;
     let request_body = options.into_request_body().map_err(|e| vec![e])?;
 
-    client
-        .first_ok(move |client, endpoint| {
+    // Each POST creates a new key, so it must not be retried.
+    let kind = if create_in_order {
+        RequestKind::NonIdempotentWrite
+    } else {
+        RequestKind::Write
+    };
+
+    let result = client
+        .first_ok_with(kind, move |client, endpoint| {
             let request_body = request_body.clone();
+            let url = build_url(endpoint, key, None);
 
             async move {
-                let url = build_url(endpoint, key, None);
                 let request = if create_in_order {
-                    client.http_client().post(url)
+                    client.http_request(Method::POST, url)
                 } else {
-                    client.http_client().put(url)
+                    client.http_request(Method::PUT, url)
                 };
                 let request = request.header(
                     http::header::CONTENT_TYPE,
-                    "application/x-www-form-urlencoded",
+                    HeaderValue::from_static("application/x-www-form-urlencoded"),
                 );
                 let response = request.body(request_body).send().await?;
                 parse_etcd_response(response, |s| {
@@ -655,7 +912,18 @@ where
                 .await
             }
         })
-        .await
+        .await;
+
+    observe_cluster_info(client, result)
+}
+
+/// Passes the cluster information from a successful response to the client.
+fn observe_cluster_info(client: &Client, result: EtcdKeyValueResult) -> EtcdKeyValueResult {
+    if let Ok(ref response) = result {
+        client.observe_cluster_info(&response.cluster_info);
+    }
+
+    result
 }
 
 /// Constructs the full URL for an API call.
@@ -0,0 +1,177 @@
+//! An in-memory mirror of an etcd directory.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use futures::{Stream, StreamExt};
+use log::error;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+
+use super::{watch_events, Action, Node, WatchError, WatchEvent, WatchOptions};
+use crate::client::Client;
+
+/// Keeps an always-current, in-memory copy of every node under an etcd directory.
+///
+/// The directory is listed once with a recursive `get` and then kept up to date by a background
+/// task that watches it for changes. If the watch falls behind etcd's event history, the
+/// directory is listed again and the copy is replaced.
+///
+/// The background task is spawned onto the current Tokio runtime and stops when the `Reflector`
+/// is dropped.
+#[derive(Debug)]
+pub struct Reflector {
+    /// Receives each new snapshot from the background task.
+    receiver: watch::Receiver<Arc<Snapshot>>,
+    /// The background task applying changes to the snapshot.
+    task: JoinHandle<()>,
+}
+
+/// The state of an etcd directory as of a specific etcd index.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Snapshot {
+    /// The etcd index the snapshot is consistent with.
+    index: u64,
+    /// Every node under the directory, keyed by its full key.
+    nodes: BTreeMap<String, Node>,
+}
+
+impl Reflector {
+    /// Lists a directory and starts mirroring it.
+    ///
+    /// Must be called from within a Tokio runtime.
+    ///
+    /// # Parameters
+    ///
+    /// * client: A `Client` to use to make the API calls.
+    /// * prefix: The name of the directory to mirror. The directory does not need to exist yet.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the directory could not be listed.
+    pub async fn new<K>(client: &Client, prefix: K) -> Result<Self, WatchError>
+    where
+        K: AsRef<str>,
+    {
+        let prefix = prefix.as_ref().trim_end_matches('/').to_owned();
+        let options = WatchOptions {
+            recursive: true,
+            ..Default::default()
+        };
+        let events = watch_events(client, &prefix, options, true);
+
+        let mut snapshot = Snapshot::default();
+        let mut events = Box::pin(events);
+        match events.next().await {
+            Some(Ok((event, index))) => snapshot.apply(&prefix, event, index),
+            Some(Err(error)) => return Err(error),
+            None => unreachable!("invariant: watch streams never end"),
+        }
+
+        let (sender, receiver) = watch::channel(Arc::new(snapshot));
+        let task = tokio::spawn(reflect(events, sender, prefix));
+
+        Ok(Reflector { receiver, task })
+    }
+
+    /// Returns the current state of the directory.
+    ///
+    /// This is cheap, and the returned snapshot does not change as the directory does.
+    pub fn snapshot(&self) -> Arc<Snapshot> {
+        self.receiver.borrow().clone()
+    }
+
+    /// Returns a receiver that is notified with a new snapshot each time the directory changes.
+    ///
+    /// The receiver stops receiving changes once the `Reflector` is dropped.
+    pub fn subscribe(&self) -> watch::Receiver<Arc<Snapshot>> {
+        self.receiver.clone()
+    }
+}
+
+impl Drop for Reflector {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+impl Snapshot {
+    /// Returns the etcd index the snapshot is consistent with.
+    pub fn index(&self) -> u64 {
+        self.index
+    }
+
+    /// Returns the node with the given key, if it exists.
+    ///
+    /// Directory nodes are returned without their children, which have their own entries.
+    pub fn get(&self, key: &str) -> Option<&Node> {
+        self.nodes.get(key)
+    }
+
+    /// Returns every node under the directory, keyed by its full key.
+    ///
+    /// Directory nodes are included without their children, which have their own entries.
+    pub fn nodes(&self) -> &BTreeMap<String, Node> {
+        &self.nodes
+    }
+
+    /// Applies a watch event for the directory `prefix` that is current as of `index`.
+    fn apply(&mut self, prefix: &str, event: WatchEvent, index: u64) {
+        match event {
+            WatchEvent::Change(kvi) => match kvi.action {
+                Action::Create | Action::Set | Action::Update | Action::CompareAndSwap => {
+                    self.insert(prefix, kvi.node)
+                }
+                Action::Delete | Action::Expire | Action::CompareAndDelete => {
+                    if let Some(key) = kvi.node.key {
+                        self.remove(&key);
+                    }
+                }
+                Action::Get => {}
+            },
+            WatchEvent::Resync(node) => {
+                self.nodes.clear();
+                self.insert(prefix, node);
+            }
+        }
+
+        self.index = index;
+    }
+
+    /// Adds a node and all of its children, skipping the directory `prefix` itself.
+    fn insert(&mut self, prefix: &str, mut node: Node) {
+        for child in node.nodes.take().unwrap_or_default() {
+            self.insert(prefix, child);
+        }
+
+        if let Some(key) = node.key.clone() {
+            if key != prefix {
+                self.nodes.insert(key, node);
+            }
+        }
+    }
+
+    /// Removes a node and all of its children.
+    fn remove(&mut self, key: &str) {
+        let children = format!("{}/", key);
+        self.nodes
+            .retain(|node_key, _| node_key != key && !node_key.starts_with(&children));
+    }
+}
+
+/// Applies each watch event to the snapshot until the `Reflector` is dropped.
+async fn reflect(
+    mut events: impl Stream<Item = Result<(WatchEvent, u64), WatchError>> + Unpin,
+    sender: watch::Sender<Arc<Snapshot>>,
+    prefix: String,
+) {
+    while let Some(item) = events.next().await {
+        match item {
+            Ok((event, index)) => sender.send_modify(|snapshot| {
+                Arc::make_mut(snapshot).apply(&prefix, event, index);
+            }),
+            // The stream already backs off before watching again after a failure.
+            Err(error) => error!("failed to watch {} for changes: {}", prefix, error),
+        }
+    }
+}
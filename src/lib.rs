@@ -23,6 +23,9 @@
 //! API, the primary key-value store API, the cluster membership API, and statistics API,
 //! respectively.
 //!
+//! The `recipes` module builds distributed coordination primitives, such as locks, on top of the
+//! key-value API. The `discovery` module uses it to register service instances and discover them.
+//!
 //! # Examples
 //!
 //! Basic usage:
@@ -56,17 +59,33 @@
 //!
 //! # Cargo features
 //!
-//! Crate `etcd` has one Cargo feature, `tls`, which adds HTTPS support via the `Client::https`
-//! constructor. This feature is enabled by default.
+//! Crate `etcd` has the following Cargo features:
+//!
+//! * tls: Adds HTTPS support via the `Client::https` constructor, using the platform's native
+//!   TLS implementation. This feature is enabled by default.
+//! * rustls-tls: Adds HTTPS support using rustls instead, which doesn't depend on OpenSSL. If both
+//!   TLS features are enabled, use `ClientBuilder::with_rustls_tls` to choose rustls.
+//! * socks: Adds support for SOCKS5 proxies to `ClientBuilder::with_proxy`.
+//! * tracing: Emits a [`tracing`](https://docs.rs/tracing) span for each API call, such as
+//!   `etcd.kv.set` or `etcd.client.health`, with a child span for each request made to an
+//!   endpoint. The child spans record the endpoint, the HTTP status, the etcd error code and the
+//!   cluster information returned.
 #![deny(missing_debug_implementations, missing_docs, warnings)]
 
-pub use crate::client::{Client, ClientBuilder, ClusterInfo, Health, Response};
+pub use crate::client::{
+    CancellationToken, Client, ClientBuilder, ClusterInfo, EndpointHealth, Health, HttpRequest,
+    HttpResponse, InterceptedRequest, Interceptor, MetricsSink, RequestMetrics, RequestOptions,
+    RequestOutcome, Response, ResponseBody, RetryPolicy, Transport,
+};
 pub use crate::error::{ApiError, Error};
 pub use crate::version::VersionInfo;
 
 pub mod auth;
+pub mod discovery;
+pub mod dns;
 pub mod kv;
 pub mod members;
+pub mod recipes;
 pub mod stats;
 
 mod client;
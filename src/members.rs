@@ -3,11 +3,11 @@
 //! These API endpoints are used to manage cluster membership.
 
 use crate::{
-    client::{parse_empty_response, parse_etcd_response},
+    client::{parse_empty_response, parse_etcd_response, RequestKind},
     Client, Error, Response,
 };
 
-use http::{StatusCode, Uri};
+use http::{Method, StatusCode, Uri};
 use serde_derive::{Deserialize, Serialize};
 use serde_json;
 
@@ -49,16 +49,25 @@ type EtcdMembersResult<T = ()> = Result<Response<T>, Vec<Error>>;
 ///
 /// * client: A `Client` to use to make the API call.
 /// * peer_urls: URLs exposing this cluster member's peer API.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(name = "etcd.members.add", skip_all)
+)]
 pub async fn add(client: &Client, peer_urls: Vec<String>) -> EtcdMembersResult {
     let peer_urls = PeerUrls { peer_urls };
     let body = serde_json::to_string(&peer_urls).map_err(|e| vec![e.into()])?;
 
+    // Adding a member is not idempotent, so it must not be retried.
     client
-        .first_ok(|client, endpoint| {
+        .first_ok_with(RequestKind::NonIdempotentWrite, |client, endpoint| {
             let body = body.clone();
+            let url = build_url(endpoint, "");
             async move {
-                let url = build_url(endpoint, "");
-                let response = client.http_client().get(url).body(body).send().await?;
+                let response = client
+                    .http_request(Method::GET, url)
+                    .body(body)
+                    .send()
+                    .await?;
                 parse_empty_response(response).await
             }
         })
@@ -71,6 +80,10 @@ pub async fn add(client: &Client, peer_urls: Vec<String>) -> EtcdMembersResult {
 ///
 /// * client: A `Client` to use to make the API call.
 /// * id: The unique identifier of the member to delete.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(name = "etcd.members.delete", skip_all, fields(id = id.as_ref()))
+)]
 pub async fn delete<K>(client: &Client, id: K) -> EtcdMembersResult
 where
     K: AsRef<str>,
@@ -80,7 +93,7 @@ where
         .first_ok(|client, endpoint| {
             let url = build_url(endpoint, &format!("/{}", id));
             async move {
-                let response = client.http_client().delete(url).send().await?;
+                let response = client.http_request(Method::DELETE, url).send().await?;
                 parse_empty_response(response).await
             }
         })
@@ -92,17 +105,23 @@ where
 /// # Parameters
 ///
 /// * client: A `Client` to use to make the API call.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(name = "etcd.members.list", skip_all)
+)]
 pub async fn list(client: &Client) -> EtcdMembersResult<Vec<Member>> {
     client
-        .first_ok(|client, endpoint| async move {
+        .first_ok(|client, endpoint| {
             let url = build_url(endpoint, "");
-            let response = client.http_client().get(url).send().await?;
-            let response: Response<ListResponse> =
-                parse_etcd_response(response, |s| s == StatusCode::OK).await?;
-            Ok(Response {
-                cluster_info: response.cluster_info,
-                data: response.data.members,
-            })
+            async move {
+                let response = client.http_request(Method::GET, url).send().await?;
+                let response: Response<ListResponse> =
+                    parse_etcd_response(response, |s| s == StatusCode::OK).await?;
+                Ok(Response {
+                    cluster_info: response.cluster_info,
+                    data: response.data.members,
+                })
+            }
         })
         .await
 }
@@ -114,6 +133,10 @@ pub async fn list(client: &Client) -> EtcdMembersResult<Vec<Member>> {
 /// * client: A `Client` to use to make the API call.
 /// * id: The unique identifier of the member to update.
 /// * peer_urls: URLs exposing this cluster member's peer API.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(name = "etcd.members.update", skip_all, fields(id = id.as_str()))
+)]
 pub async fn update(client: &Client, id: String, peer_urls: Vec<String>) -> EtcdMembersResult {
     let peer_urls = PeerUrls { peer_urls };
     let body = serde_json::to_string(&peer_urls).map_err(|e| vec![e.into()])?;
@@ -123,7 +146,11 @@ pub async fn update(client: &Client, id: String, peer_urls: Vec<String>) -> Etcd
             let url = build_url(endpoint, &format!("/{}", id));
             let body = body.clone();
             async move {
-                let response = client.http_client().put(url).body(body).send().await?;
+                let response = client
+                    .http_request(Method::PUT, url)
+                    .body(body)
+                    .send()
+                    .await?;
                 parse_empty_response(response).await
             }
         })
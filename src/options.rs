@@ -71,6 +71,10 @@ impl GetOptions {
 
         serializer.append_pair("recursive", bool_to_str(self.recursive));
 
+        if self.strong_consistency {
+            serializer.append_pair("quorum", bool_to_str(true));
+        }
+
         if let Some(sort) = self.sort {
             serializer.append_pair("sorted", bool_to_str(sort));
         }
@@ -0,0 +1,270 @@
+//! Barriers for synchronizing groups of processes.
+//!
+//! A `Barrier` blocks processes until a single key is deleted. A `DoubleBarrier` blocks
+//! participants from entering a computation until enough of them are ready, and from leaving it
+//! until all of them are done.
+//!
+//! Keys created by either barrier have a TTL and are refreshed while held, so a process that dies
+//! stops holding a barrier or counting as a participant once its key expires. The key marking a
+//! `DoubleBarrier` as entered is refreshed by every participant inside it, so it expires if they
+//! all die without leaving, and the next participants wait for each other again.
+
+use crate::client::Client;
+use crate::error::Error;
+use crate::kv;
+
+use super::{
+    delete_in_background, is_key_already_exists, is_key_not_found, list_in_order, wait_for_change,
+    wait_for_deletion, KeepAlive,
+};
+
+/// A barrier that blocks processes until it is released.
+///
+/// The barrier is held for as long as its key exists.
+#[derive(Debug)]
+pub struct Barrier {
+    /// The client used to make API calls.
+    client: Client,
+    /// The barrier's key.
+    key: String,
+    /// The TTL of the key, in seconds.
+    ttl: u64,
+    /// Keeps the key alive while this process holds the barrier.
+    keep_alive: Option<KeepAlive>,
+}
+
+/// A barrier that synchronizes a fixed number of participants entering and leaving a computation.
+#[derive(Debug)]
+pub struct DoubleBarrier {
+    /// The client used to make API calls.
+    client: Client,
+    /// The directory used to coordinate the barrier.
+    dir: String,
+    /// The number of participants needed to enter the barrier.
+    count: usize,
+    /// The TTL of the key representing each participant, in seconds.
+    ttl: u64,
+    /// The key representing this participant while it is inside the barrier, and the task keeping
+    /// it alive.
+    entry: Option<(String, KeepAlive)>,
+    /// Keeps the key marking the barrier as entered alive while this participant is inside it.
+    ready: Option<KeepAlive>,
+}
+
+impl Barrier {
+    /// Creates a new barrier.
+    ///
+    /// # Parameters
+    ///
+    /// * client: A `Client` to use to make the API calls.
+    /// * key: The barrier's key. All processes using the same key share the same barrier.
+    /// * ttl: The TTL of the key while held, in seconds. If the holder dies, the barrier will be
+    ///   released after at most this long.
+    pub fn new<K>(client: &Client, key: K, ttl: u64) -> Self
+    where
+        K: AsRef<str>,
+    {
+        Barrier {
+            client: client.clone(),
+            key: key.as_ref().to_owned(),
+            ttl,
+            keep_alive: None,
+        }
+    }
+
+    /// Returns the barrier's key.
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Holds the barrier, blocking other processes that wait on it until it is released.
+    ///
+    /// The barrier is released when this value is dropped.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the key could not be set.
+    pub async fn hold(&mut self) -> Result<(), Vec<Error>> {
+        let response = kv::set(&self.client, &self.key, "", Some(self.ttl)).await?;
+        self.keep_alive = Some(KeepAlive::spawn(
+            &self.client,
+            &self.key,
+            self.ttl,
+            response.data.node.created_index,
+        ));
+        Ok(())
+    }
+
+    /// Releases the barrier, unblocking every process waiting on it.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the key could not be deleted. The barrier will still be released once the key
+    /// expires.
+    pub async fn release(&mut self) -> Result<(), Vec<Error>> {
+        self.keep_alive = None;
+        match kv::delete(&self.client, &self.key, false).await {
+            Ok(_) => Ok(()),
+            Err(ref errors) if is_key_not_found(errors) => Ok(()),
+            Err(errors) => Err(errors),
+        }
+    }
+
+    /// Waits until the barrier is not held.
+    ///
+    /// Returns immediately if the barrier is not held when called.
+    ///
+    /// # Errors
+    ///
+    /// Fails if any API call fails.
+    pub async fn wait(&self) -> Result<(), Vec<Error>> {
+        loop {
+            match kv::get(&self.client, &self.key, Default::default()).await {
+                Ok(response) => wait_for_deletion(&self.client, &response.data.node).await?,
+                Err(ref errors) if is_key_not_found(errors) => return Ok(()),
+                Err(errors) => return Err(errors),
+            }
+        }
+    }
+}
+
+impl Drop for Barrier {
+    fn drop(&mut self) {
+        if self.keep_alive.is_some() {
+            delete_in_background(&self.client, &self.key);
+        }
+    }
+}
+
+impl DoubleBarrier {
+    /// Creates a new double barrier.
+    ///
+    /// The barrier can be used again once every participant has left it.
+    ///
+    /// # Parameters
+    ///
+    /// * client: A `Client` to use to make the API calls.
+    /// * dir: The directory used to coordinate the barrier. All processes using the same directory
+    ///   take part in the same barrier.
+    /// * count: The number of participants needed to enter the barrier.
+    /// * ttl: The TTL of the key representing each participant, in seconds. If a participant dies,
+    ///   it stops counting towards the barrier after at most this long.
+    pub fn new<K>(client: &Client, dir: K, count: usize, ttl: u64) -> Self
+    where
+        K: AsRef<str>,
+    {
+        DoubleBarrier {
+            client: client.clone(),
+            dir: dir.as_ref().trim_end_matches('/').to_owned(),
+            count,
+            ttl,
+            entry: None,
+            ready: None,
+        }
+    }
+
+    /// Registers this process as a participant and waits until enough participants have entered.
+    ///
+    /// # Errors
+    ///
+    /// Fails if any API call fails.
+    pub async fn enter(&mut self) -> Result<(), Vec<Error>> {
+        let waiters_dir = self.waiters_dir();
+        let ready_key = self.ready_key();
+
+        if self.entry.is_none() {
+            let response =
+                kv::create_in_order(&self.client, &waiters_dir, "", Some(self.ttl)).await?;
+            let node = response.data.node;
+            let key = node
+                .key
+                .expect("invariant: created nodes should always have a key");
+            let keep_alive = KeepAlive::spawn(&self.client, &key, self.ttl, node.created_index);
+            self.entry = Some((key, keep_alive));
+        }
+
+        loop {
+            match kv::get(&self.client, &ready_key, Default::default()).await {
+                Ok(response) => {
+                    self.keep_ready_alive(response.data.node.created_index);
+                    return Ok(());
+                }
+                Err(ref errors) if is_key_not_found(errors) => {}
+                Err(errors) => return Err(errors),
+            }
+
+            let (waiters, index) = list_in_order(&self.client, &waiters_dir).await?;
+            if waiters.len() >= self.count {
+                match kv::create(&self.client, &ready_key, "", Some(self.ttl)).await {
+                    Ok(response) => {
+                        self.keep_ready_alive(response.data.node.created_index);
+                        return Ok(());
+                    }
+                    // Another participant created it first, so it is read again.
+                    Err(ref errors) if is_key_already_exists(errors) => continue,
+                    Err(errors) => return Err(errors),
+                }
+            }
+
+            wait_for_change(&self.client, &self.dir, index).await?;
+        }
+    }
+
+    /// Starts refreshing the key marking the barrier as entered while this participant is inside
+    /// it.
+    fn keep_ready_alive(&mut self, created_index: Option<u64>) {
+        self.ready = Some(KeepAlive::spawn(
+            &self.client,
+            &self.ready_key(),
+            self.ttl,
+            created_index,
+        ));
+    }
+
+    /// Unregisters this process as a participant and waits until every participant has left.
+    ///
+    /// # Errors
+    ///
+    /// Fails if any API call fails.
+    pub async fn leave(&mut self) -> Result<(), Vec<Error>> {
+        self.ready = None;
+        if let Some((key, _)) = self.entry.take() {
+            match kv::delete(&self.client, &key, false).await {
+                Ok(_) => {}
+                Err(ref errors) if is_key_not_found(errors) => {}
+                Err(errors) => return Err(errors),
+            }
+        }
+
+        loop {
+            let (waiters, index) = list_in_order(&self.client, &self.waiters_dir()).await?;
+            if waiters.is_empty() {
+                return match kv::delete(&self.client, self.ready_key(), false).await {
+                    Ok(_) => Ok(()),
+                    Err(ref errors) if is_key_not_found(errors) => Ok(()),
+                    Err(errors) => Err(errors),
+                };
+            }
+
+            wait_for_change(&self.client, &self.dir, index).await?;
+        }
+    }
+
+    /// Returns the directory holding one key for each participant.
+    fn waiters_dir(&self) -> String {
+        format!("{}/waiters", self.dir)
+    }
+
+    /// Returns the key created once enough participants have entered the barrier.
+    fn ready_key(&self) -> String {
+        format!("{}/ready", self.dir)
+    }
+}
+
+impl Drop for DoubleBarrier {
+    fn drop(&mut self) {
+        if let Some((ref key, _)) = self.entry {
+            delete_in_background(&self.client, key);
+        }
+    }
+}
@@ -0,0 +1,231 @@
+//! Leader election.
+//!
+//! The leader of an election is the process that created the election's leader key with
+//! `kv::create`. Candidates that fail to create the key watch it and try again once it is deleted
+//! or expires. The leader keeps the key alive with `kv::refresh` and loses leadership if the key
+//! can no longer be refreshed.
+
+use futures::future;
+use futures::stream::{Stream, StreamExt};
+
+use crate::client::Client;
+use crate::error::Error;
+use crate::kv::{self, watch_events, Action, WatchError, WatchEvent, WatchOptions};
+
+use super::{is_key_already_exists, is_key_not_found, wait_for_deletion, KeepAlive};
+
+/// Leadership of an election, held by the process that won a `campaign`.
+///
+/// Leadership is resigned when this value is dropped.
+#[derive(Debug)]
+pub struct Leader {
+    /// The client used to make API calls.
+    client: Client,
+    /// The election's leader key.
+    key: String,
+    /// The value currently proclaimed by the leader.
+    value: String,
+    /// The TTL of the leader key, in seconds.
+    ttl: u64,
+    /// Keeps the leader key alive while leadership is held.
+    keep_alive: KeepAlive,
+    /// Whether leadership has already been resigned.
+    resigned: bool,
+}
+
+/// Waits until this process is elected leader.
+///
+/// If the returned future is dropped before it completes, this process stops campaigning.
+///
+/// # Parameters
+///
+/// * client: A `Client` to use to make the API calls.
+/// * election_dir: The directory used to coordinate the election. All processes using the same
+///   directory take part in the same election.
+/// * value: The value proclaimed while this process is leader, typically an address other
+///   processes can use to reach it. It should be unique to this process.
+/// * ttl: The TTL of the leader key, in seconds. If the leader dies, a new leader will be elected
+///   after at most this long.
+///
+/// # Errors
+///
+/// Fails if any API call fails.
+pub async fn campaign<K, V>(
+    client: &Client,
+    election_dir: K,
+    value: V,
+    ttl: u64,
+) -> Result<Leader, Vec<Error>>
+where
+    K: AsRef<str>,
+    V: Into<String>,
+{
+    let key = leader_key(election_dir.as_ref());
+    let value = value.into();
+
+    loop {
+        match kv::create(client, &key, &value, Some(ttl)).await {
+            Ok(response) => {
+                let created_index = response.data.node.created_index;
+                return Ok(Leader {
+                    keep_alive: KeepAlive::spawn(client, &key, ttl, created_index),
+                    client: client.clone(),
+                    key,
+                    value,
+                    ttl,
+                    resigned: false,
+                });
+            }
+            Err(ref errors) if is_key_already_exists(errors) => {}
+            Err(errors) => return Err(errors),
+        }
+
+        match kv::get(client, &key, Default::default()).await {
+            Ok(response) => wait_for_deletion(client, &response.data.node).await?,
+            Err(ref errors) if is_key_not_found(errors) => {}
+            Err(errors) => return Err(errors),
+        }
+    }
+}
+
+/// Observes the value proclaimed by the leader of an election.
+///
+/// The stream first yields the current value, then each new value as it changes. `None` means
+/// that the election currently has no leader. The stream never ends on its own; drop it to stop
+/// observing.
+///
+/// # Parameters
+///
+/// * client: A `Client` to use to make the API calls.
+/// * election_dir: The directory used to coordinate the election.
+///
+/// # Errors
+///
+/// Failed requests are yielded as errors without ending the stream.
+pub fn observe<K>(
+    client: &Client,
+    election_dir: K,
+) -> impl Stream<Item = Result<Option<String>, WatchError>>
+where
+    K: AsRef<str>,
+{
+    let key = leader_key(election_dir.as_ref());
+    let mut last = None;
+
+    watch_events(client, &key, WatchOptions::default(), true).filter_map(move |result| {
+        let item = match result {
+            Ok((event, _)) => {
+                let value = leader_value(event);
+                if last.as_ref() == Some(&value) {
+                    None
+                } else {
+                    last = Some(value.clone());
+                    Some(Ok(value))
+                }
+            }
+            Err(error) => Some(Err(error)),
+        };
+
+        future::ready(item)
+    })
+}
+
+impl Leader {
+    /// Returns the election's leader key.
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Returns the value currently proclaimed by this leader.
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    /// Returns whether this process is still known to be leader.
+    ///
+    /// Leadership is lost if the leader key could not be refreshed before it expired, or if it was
+    /// deleted by another process.
+    pub fn is_leader(&self) -> bool {
+        self.keep_alive.is_alive()
+    }
+
+    /// Resolves once leadership has been lost.
+    ///
+    /// Leaders should stop acting as leader as soon as this resolves.
+    pub async fn lost(&self) {
+        self.keep_alive.lost().await
+    }
+
+    /// Changes the value proclaimed by this leader without giving up leadership.
+    ///
+    /// # Errors
+    ///
+    /// Fails if this process is no longer leader.
+    pub async fn proclaim<V>(&mut self, value: V) -> Result<(), Vec<Error>>
+    where
+        V: Into<String>,
+    {
+        let value = value.into();
+        kv::compare_and_swap(
+            &self.client,
+            &self.key,
+            &value,
+            Some(self.ttl),
+            Some(&self.value),
+            None,
+        )
+        .await?;
+        self.value = value;
+        Ok(())
+    }
+
+    /// Gives up leadership, waiting for the leader key to be deleted.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the leader key could not be deleted. Leadership will still end once the key
+    /// expires.
+    pub async fn resign(mut self) -> Result<(), Vec<Error>> {
+        self.resigned = true;
+        match kv::compare_and_delete(&self.client, &self.key, Some(&self.value), None).await {
+            Ok(_) => Ok(()),
+            Err(ref errors) if is_key_not_found(errors) => Ok(()),
+            Err(errors) => Err(errors),
+        }
+    }
+}
+
+impl Drop for Leader {
+    fn drop(&mut self) {
+        if self.resigned {
+            return;
+        }
+
+        if let Ok(handle) = tokio::runtime::Handle::try_current() {
+            let client = self.client.clone();
+            let key = self.key.clone();
+            let value = self.value.clone();
+            handle.spawn(async move {
+                kv::compare_and_delete(&client, &key, Some(&value), None)
+                    .await
+                    .ok();
+            });
+        }
+    }
+}
+
+/// Returns the leader key for an election directory.
+fn leader_key(election_dir: &str) -> String {
+    format!("{}/leader", election_dir.trim_end_matches('/'))
+}
+
+/// Returns the value proclaimed by the leader after a change to the leader key.
+fn leader_value(event: WatchEvent) -> Option<String> {
+    match event {
+        WatchEvent::Change(kvi) => match kvi.action {
+            Action::Delete | Action::Expire | Action::CompareAndDelete => None,
+            _ => kvi.node.value,
+        },
+        WatchEvent::Resync(node) => node.value,
+    }
+}
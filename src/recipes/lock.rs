@@ -0,0 +1,130 @@
+//! A distributed mutual exclusion lock.
+//!
+//! Each process trying to take the lock creates a key in the lock directory with
+//! `kv::create_in_order`. The process whose key was created first holds the lock. Every other
+//! process watches the key created just before its own, and checks again once that key is gone.
+//! Keys have a TTL and are refreshed while held, so the lock is released by etcd if its holder
+//! dies.
+
+use crate::client::Client;
+use crate::error::Error;
+
+use super::{list_in_order, wait_for_deletion, HeldKey};
+
+/// A distributed mutual exclusion lock backed by an etcd directory.
+#[derive(Clone, Debug)]
+pub struct Mutex {
+    /// The client used to make API calls.
+    client: Client,
+    /// The directory holding one key for each process holding or waiting for the lock.
+    dir: String,
+    /// The TTL of each key, in seconds.
+    ttl: u64,
+}
+
+/// Proof that a `Mutex` is held. The lock is released when the guard is dropped.
+#[derive(Debug)]
+pub struct MutexGuard {
+    /// The key owned by this guard.
+    key: HeldKey,
+}
+
+impl Mutex {
+    /// Creates a new mutex.
+    ///
+    /// # Parameters
+    ///
+    /// * client: A `Client` to use to make the API calls.
+    /// * dir: The directory used to coordinate the lock. All processes using the same directory
+    ///   contend for the same lock.
+    /// * ttl: The TTL of the key representing each process, in seconds. If a process dies, the
+    ///   lock will be released after at most this long.
+    pub fn new<K>(client: &Client, dir: K, ttl: u64) -> Self
+    where
+        K: AsRef<str>,
+    {
+        Mutex {
+            client: client.clone(),
+            dir: dir.as_ref().trim_end_matches('/').to_owned(),
+            ttl,
+        }
+    }
+
+    /// Waits until the lock is acquired.
+    ///
+    /// If the returned future is dropped before it completes, this process stops waiting for the
+    /// lock.
+    ///
+    /// # Errors
+    ///
+    /// Fails if any API call fails.
+    pub async fn lock(&self) -> Result<MutexGuard, Vec<Error>> {
+        loop {
+            let key = HeldKey::create_in_order(&self.client, &self.dir, "", self.ttl).await?;
+            let guard = MutexGuard { key };
+
+            if self.wait_for_turn(&guard).await? {
+                return Ok(guard);
+            }
+        }
+    }
+
+    /// Waits until the guard's key is the oldest in the lock directory.
+    ///
+    /// Returns false if the key disappeared while waiting, in which case it must be created again.
+    async fn wait_for_turn(&self, guard: &MutexGuard) -> Result<bool, Vec<Error>> {
+        loop {
+            let (nodes, _) = list_in_order(&self.client, &self.dir).await?;
+            let position = match nodes
+                .iter()
+                .position(|node| node.key.as_deref() == Some(guard.key()))
+            {
+                Some(position) => position,
+                None => return Ok(false),
+            };
+
+            if position == 0 {
+                return Ok(true);
+            }
+
+            wait_for_deletion(&self.client, &nodes[position - 1]).await?;
+        }
+    }
+}
+
+impl MutexGuard {
+    /// Returns the key representing this process in the lock directory.
+    pub fn key(&self) -> &str {
+        self.key.key()
+    }
+
+    /// Returns the modified index of this process's key when the lock was requested.
+    ///
+    /// The index increases every time the lock changes hands, so it can be passed along to other
+    /// systems as a fencing token to reject requests from previous holders of the lock.
+    pub fn modified_index(&self) -> u64 {
+        self.key.modified_index()
+    }
+
+    /// Returns whether the lock is still known to be held.
+    ///
+    /// The lock is lost if this process's key could not be refreshed before it expired.
+    pub fn is_held(&self) -> bool {
+        self.key.is_alive()
+    }
+
+    /// Resolves once the lock has been lost because this process's key expired.
+    pub async fn lost(&self) {
+        self.key.lost().await
+    }
+
+    /// Releases the lock, waiting for the key to be deleted.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the key could not be deleted. The lock will still be released once the key
+    /// expires.
+    pub async fn unlock(self) -> Result<(), Vec<Error>> {
+        self.key.release().await
+    }
+}
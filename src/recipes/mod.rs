@@ -0,0 +1,334 @@
+//! Distributed coordination recipes built on etcd's key-value API.
+//!
+//! Each recipe is implemented entirely with the functions in the `kv` module, using the
+//! algorithms documented for etcd's v2 API. Keys owned by a recipe are created with a TTL and kept
+//! alive by a background task, so they are cleaned up by etcd if the owning process dies.
+//!
+//! Recipes spawn background tasks onto the current Tokio runtime, so they must be used from
+//! within one.
+
+use std::time::{Duration, Instant};
+
+use log::error;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+
+use crate::client::Client;
+use crate::error::Error;
+use crate::kv::{self, key_not_found_index, Action, GetOptions, Node, WatchOptions};
+
+pub mod barrier;
+pub mod election;
+pub mod lock;
+pub mod queue;
+pub mod rwlock;
+pub mod semaphore;
+
+/// The etcd error code returned when a compare-and-swap or compare-and-delete condition fails.
+const COMPARE_FAILED: u64 = 101;
+
+/// The etcd error code returned when creating a key that already exists.
+const KEY_ALREADY_EXISTS: u64 = 105;
+
+/// The shortest time a `KeepAlive` waits between refreshes, so that very short TTLs don't make it
+/// refresh its key in a tight loop.
+const MIN_REFRESH_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Keeps a key alive by refreshing its TTL in the background until dropped.
+#[derive(Debug)]
+pub(crate) struct KeepAlive {
+    /// Whether the key is still known to be alive.
+    alive: watch::Receiver<bool>,
+    /// The background task refreshing the key.
+    task: JoinHandle<()>,
+}
+
+impl KeepAlive {
+    /// Starts refreshing `key` every third of `ttl` seconds.
+    ///
+    /// The key is considered lost if it no longer exists, if it has been recreated by someone
+    /// else, as determined by its created index no longer matching `created_index`, or if it could
+    /// not be refreshed for `ttl` seconds, after which etcd will have expired it.
+    pub(crate) fn spawn(client: &Client, key: &str, ttl: u64, created_index: Option<u64>) -> Self {
+        let (sender, alive) = watch::channel(true);
+        let task = tokio::spawn(refresh(
+            client.clone(),
+            key.to_owned(),
+            ttl,
+            created_index,
+            sender,
+        ));
+
+        KeepAlive { alive, task }
+    }
+
+    /// Returns whether the key is still known to be alive.
+    pub(crate) fn is_alive(&self) -> bool {
+        *self.alive.borrow()
+    }
+
+    /// Resolves once the key has been lost.
+    pub(crate) async fn lost(&self) {
+        let mut alive = self.alive.clone();
+        while *alive.borrow() {
+            if alive.changed().await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+impl Drop for KeepAlive {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// A key created in a recipe's directory to represent this process, such as a lock holder or a
+/// permit holder.
+///
+/// The key is kept alive until it is released, and deleted in the background if it is dropped
+/// without being released.
+#[derive(Debug)]
+pub(crate) struct HeldKey {
+    /// The client used to make API calls.
+    client: Client,
+    /// The key.
+    key: String,
+    /// The modified index of the key when it was created.
+    modified_index: u64,
+    /// Keeps the key alive until it is released.
+    keep_alive: KeepAlive,
+    /// Whether the key has already been deleted.
+    released: bool,
+}
+
+impl HeldKey {
+    /// Creates a key in `dir` with `kv::create_in_order` and starts keeping it alive.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the key could not be created.
+    pub(crate) async fn create_in_order(
+        client: &Client,
+        dir: &str,
+        value: &str,
+        ttl: u64,
+    ) -> Result<Self, Vec<Error>> {
+        let response = kv::create_in_order(client, dir, value, Some(ttl)).await?;
+        let node = response.data.node;
+        let key = node
+            .key
+            .expect("invariant: created nodes should always have a key");
+
+        Ok(HeldKey {
+            keep_alive: KeepAlive::spawn(client, &key, ttl, node.created_index),
+            client: client.clone(),
+            key,
+            modified_index: node.modified_index.unwrap_or_default(),
+            released: false,
+        })
+    }
+
+    /// Returns the key.
+    pub(crate) fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Returns the modified index of the key when it was created.
+    pub(crate) fn modified_index(&self) -> u64 {
+        self.modified_index
+    }
+
+    /// Returns whether the key is still known to be alive.
+    pub(crate) fn is_alive(&self) -> bool {
+        self.keep_alive.is_alive()
+    }
+
+    /// Resolves once the key has been lost.
+    pub(crate) async fn lost(&self) {
+        self.keep_alive.lost().await
+    }
+
+    /// Deletes the key, waiting for the deletion to complete.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the key could not be deleted, in which case it is left to expire.
+    pub(crate) async fn release(mut self) -> Result<(), Vec<Error>> {
+        self.released = true;
+        kv::delete(&self.client, &self.key, false).await?;
+        Ok(())
+    }
+}
+
+impl Drop for HeldKey {
+    fn drop(&mut self) {
+        if !self.released {
+            delete_in_background(&self.client, &self.key);
+        }
+    }
+}
+
+/// Refreshes a key until it is lost, reporting the loss through `alive`.
+async fn refresh(
+    client: Client,
+    key: String,
+    ttl: u64,
+    created_index: Option<u64>,
+    alive: watch::Sender<bool>,
+) {
+    let lifetime = Duration::from_secs(ttl);
+    let interval = refresh_interval(ttl);
+    // The key was created just before the task started, so it lives until at least `lifetime`
+    // after now.
+    let mut refreshed_at = Instant::now();
+
+    loop {
+        tokio::time::sleep(interval).await;
+
+        // A refresh that is still in progress when the key expires could not have kept it alive.
+        let sent_at = Instant::now();
+        let expires_at = refreshed_at + lifetime;
+        match tokio::time::timeout_at(expires_at.into(), kv::refresh(&client, &key, ttl)).await {
+            Ok(Ok(response)) => {
+                if created_index.is_some() && response.data.node.created_index != created_index {
+                    break;
+                }
+                refreshed_at = sent_at;
+            }
+            Ok(Err(ref errors)) if is_key_not_found(errors) => break,
+            Ok(Err(errors)) => error!("failed to refresh {}: {:?}", key, errors),
+            Err(_) => error!("timed out refreshing {}", key),
+        }
+
+        if refreshed_at.elapsed() >= lifetime {
+            error!("{} expired before it could be refreshed", key);
+            break;
+        }
+    }
+
+    alive.send(false).ok();
+}
+
+/// Returns how long to wait between refreshes of a key with the given TTL, in seconds.
+pub(crate) fn refresh_interval(ttl: u64) -> Duration {
+    (Duration::from_secs(ttl) / 3).max(MIN_REFRESH_INTERVAL)
+}
+
+/// Deletes a key from a background task, for use where the deletion cannot be awaited.
+///
+/// Does nothing outside of a Tokio runtime, in which case the key is left to expire.
+pub(crate) fn delete_in_background(client: &Client, key: &str) {
+    if let Ok(handle) = tokio::runtime::Handle::try_current() {
+        let client = client.clone();
+        let key = key.to_owned();
+        handle.spawn(async move {
+            if let Err(errors) = kv::delete(&client, &key, false).await {
+                if !is_key_not_found(&errors) {
+                    error!("failed to delete {}: {:?}", key, errors);
+                }
+            }
+        });
+    }
+}
+
+/// Lists the children of a directory in the order they were created.
+///
+/// A directory that does not exist is treated as empty. Returns the children along with the etcd
+/// index the listing is current as of.
+pub(crate) async fn list_in_order(
+    client: &Client,
+    dir: &str,
+) -> Result<(Vec<Node>, u64), Vec<Error>> {
+    let options = GetOptions {
+        sort: true,
+        ..Default::default()
+    };
+
+    match kv::get(client, dir, options).await {
+        Ok(response) => {
+            let mut nodes = response.data.node.nodes.unwrap_or_default();
+            nodes.sort_by_key(|node| node.created_index);
+            let index = response.cluster_info.etcd_index.unwrap_or_default();
+            Ok((nodes, index))
+        }
+        Err(errors) => match key_not_found_index(&errors) {
+            Some(index) => Ok((Vec::new(), index)),
+            None => Err(errors),
+        },
+    }
+}
+
+/// Waits until a node is deleted or expires.
+///
+/// Also returns if the watch falls behind etcd's event history, so callers should check the state
+/// of the node again afterwards.
+pub(crate) async fn wait_for_deletion(client: &Client, node: &Node) -> Result<(), Vec<Error>> {
+    let key = match node.key {
+        Some(ref key) => key,
+        None => return Ok(()),
+    };
+    let mut index = node.modified_index.map(|index| index + 1);
+
+    loop {
+        let options = WatchOptions {
+            index,
+            ..Default::default()
+        };
+
+        match kv::watch(client, key, options).await {
+            Ok(response) => match response.data.action {
+                Action::Delete | Action::Expire | Action::CompareAndDelete => return Ok(()),
+                _ => index = response.data.node.modified_index.map(|index| index + 1),
+            },
+            Err(ref error) if error.is_event_index_cleared() => return Ok(()),
+            Err(kv::WatchError::Other(errors)) => return Err(errors),
+            Err(kv::WatchError::Timeout) => {}
+        }
+    }
+}
+
+/// Waits until anything under a directory changes after the etcd index `index`.
+///
+/// Also returns if the watch falls behind etcd's event history, so callers should check the state
+/// of the directory again afterwards.
+pub(crate) async fn wait_for_change(
+    client: &Client,
+    dir: &str,
+    index: u64,
+) -> Result<(), Vec<Error>> {
+    let options = WatchOptions {
+        index: Some(index + 1),
+        recursive: true,
+        ..Default::default()
+    };
+
+    match kv::watch(client, dir, options).await {
+        Ok(_) => Ok(()),
+        Err(ref error) if error.is_event_index_cleared() => Ok(()),
+        Err(kv::WatchError::Other(errors)) => Err(errors),
+        Err(kv::WatchError::Timeout) => Ok(()),
+    }
+}
+
+/// Returns whether a request failed because the key does not exist.
+pub(crate) fn is_key_not_found(errors: &[Error]) -> bool {
+    key_not_found_index(errors).is_some()
+}
+
+/// Returns whether a request failed because its compare condition did not hold.
+pub(crate) fn is_compare_failed(errors: &[Error]) -> bool {
+    errors.iter().any(|error| match *error {
+        Error::Api(ref error) => error.error_code == COMPARE_FAILED,
+        _ => false,
+    })
+}
+
+/// Returns whether a request failed because the key already exists.
+pub(crate) fn is_key_already_exists(errors: &[Error]) -> bool {
+    errors.iter().any(|error| match *error {
+        Error::Api(ref error) => error.error_code == KEY_ALREADY_EXISTS,
+        _ => false,
+    })
+}
@@ -0,0 +1,318 @@
+//! A distributed work queue.
+//!
+//! Items are pushed into the queue's `items` directory with `kv::create_in_order`, and are popped
+//! in the order they were created. By default, popping an item claims it by deleting it with
+//! `kv::compare_and_delete`, so each item is delivered to exactly one consumer.
+//!
+//! In visibility-timeout mode, popping an item instead claims it by creating a marker key with
+//! the same name in the queue's `inflight` directory. The marker has a TTL, and the item stays in
+//! the queue but is skipped by other consumers for as long as the marker exists. Once the item is
+//! acked, both keys are deleted. If the consumer dies or takes too long, the marker expires and
+//! the item becomes visible in the queue again, in its original position.
+//!
+//! The item is not moved into the `inflight` directory itself. etcd's v2 API cannot move a key
+//! atomically, and an expired in-flight copy would only make it back into the queue if some
+//! process were watching for the expiry and re-created it, at the back of the queue. With a
+//! marker, an item whose consumer dies is never at risk of being lost.
+
+use std::collections::HashSet;
+
+use crate::client::Client;
+use crate::error::Error;
+use crate::kv::{self, key_not_found_index, GetOptions, Node};
+
+use super::{is_compare_failed, is_key_already_exists, is_key_not_found, wait_for_change};
+
+/// A distributed work queue backed by an etcd directory.
+#[derive(Clone, Debug)]
+pub struct Queue {
+    /// The client used to make API calls.
+    client: Client,
+    /// The directory holding the queue.
+    dir: String,
+    /// How long a popped item stays claimed before it becomes visible again, in seconds.
+    visibility_timeout: Option<u64>,
+}
+
+/// An item popped from a `Queue`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Item {
+    /// The key of the item in the queue.
+    key: String,
+    /// The value of the item.
+    value: String,
+    /// The key and modified index of the marker claiming the item, in visibility-timeout
+    /// mode.
+    claim: Option<(String, u64)>,
+}
+
+impl Queue {
+    /// Creates a new queue whose items are removed as soon as they are popped.
+    ///
+    /// # Parameters
+    ///
+    /// * client: A `Client` to use to make the API calls.
+    /// * dir: The directory holding the queue. All processes using the same directory share the
+    ///   same queue.
+    pub fn new<K>(client: &Client, dir: K) -> Self
+    where
+        K: AsRef<str>,
+    {
+        Queue {
+            client: client.clone(),
+            dir: dir.as_ref().trim_end_matches('/').to_owned(),
+            visibility_timeout: None,
+        }
+    }
+
+    /// Creates a new queue whose items must be acked after they are popped.
+    ///
+    /// # Parameters
+    ///
+    /// * client: A `Client` to use to make the API calls.
+    /// * dir: The directory holding the queue. All processes using the same directory share the
+    ///   same queue.
+    /// * visibility_timeout: How long a popped item stays hidden from other consumers, in seconds.
+    ///   Items that are not acked within this time are delivered again.
+    pub fn with_visibility_timeout<K>(client: &Client, dir: K, visibility_timeout: u64) -> Self
+    where
+        K: AsRef<str>,
+    {
+        Queue {
+            visibility_timeout: Some(visibility_timeout),
+            ..Queue::new(client, dir)
+        }
+    }
+
+    /// Adds an item to the back of the queue, returning its key.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the item could not be created.
+    pub async fn push<V>(&self, value: V) -> Result<String, Vec<Error>>
+    where
+        V: AsRef<str>,
+    {
+        let response = kv::create_in_order(&self.client, self.items_dir(), value, None).await?;
+        Ok(response
+            .data
+            .node
+            .key
+            .expect("invariant: created nodes should always have a key"))
+    }
+
+    /// Waits until an item is available and removes it from the front of the queue.
+    ///
+    /// This is not cancel-safe for queues without a visibility timeout. If the returned future is
+    /// dropped while an item is being deleted, etcd may delete the item without it being returned,
+    /// and the item is lost. In visibility-timeout mode, an item claimed by a dropped future is
+    /// delivered again once its visibility timeout passes.
+    ///
+    /// # Errors
+    ///
+    /// Fails if any API call fails.
+    pub async fn pop(&self) -> Result<Item, Vec<Error>> {
+        loop {
+            let (item, index) = self.claim_first().await?;
+            if let Some(item) = item {
+                return Ok(item);
+            }
+
+            wait_for_change(&self.client, &self.dir, index).await?;
+        }
+    }
+
+    /// Removes the item at the front of the queue, or returns `None` if the queue is empty.
+    ///
+    /// As with `Queue::pop`, an item may be lost if the returned future is dropped before it
+    /// completes, unless the queue has a visibility timeout.
+    ///
+    /// # Errors
+    ///
+    /// Fails if any API call fails.
+    pub async fn try_pop(&self) -> Result<Option<Item>, Vec<Error>> {
+        Ok(self.claim_first().await?.0)
+    }
+
+    /// Marks a popped item as processed, removing it from the queue for good.
+    ///
+    /// Does nothing unless the queue has a visibility timeout, since items are otherwise removed as
+    /// soon as they are popped.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the item's visibility timeout has already passed, in which case it may have been
+    /// delivered to another consumer.
+    pub async fn ack(&self, item: &Item) -> Result<(), Vec<Error>> {
+        let (claim_key, claim_index) = match item.claim {
+            Some((ref key, index)) => (key, index),
+            None => return Ok(()),
+        };
+
+        // Extend the claim first, which fails if it was lost, so that the item cannot be claimed by
+        // anyone else while it is being deleted.
+        let response = kv::compare_and_swap(
+            &self.client,
+            claim_key,
+            &item.value,
+            self.visibility_timeout,
+            None,
+            Some(claim_index),
+        )
+        .await?;
+
+        match kv::delete(&self.client, &item.key, false).await {
+            Ok(_) => {}
+            Err(ref errors) if is_key_not_found(errors) => {}
+            Err(errors) => return Err(errors),
+        }
+
+        let claim_index = response.data.node.modified_index;
+        match kv::compare_and_delete(&self.client, claim_key, None, claim_index).await {
+            Ok(_) => Ok(()),
+            Err(ref errors) if is_key_not_found(errors) || is_compare_failed(errors) => Ok(()),
+            Err(errors) => Err(errors),
+        }
+    }
+
+    /// Claims the oldest unclaimed item in the queue.
+    ///
+    /// Returns the claimed item, if any, along with the etcd index the queue was listed at.
+    async fn claim_first(&self) -> Result<(Option<Item>, u64), Vec<Error>> {
+        let (items, claimed, index) = self.list().await?;
+
+        for node in items {
+            if claimed.contains(name(&node)) {
+                continue;
+            }
+
+            if let Some(item) = self.claim(node).await? {
+                return Ok((Some(item), index));
+            }
+        }
+
+        Ok((None, index))
+    }
+
+    /// Lists the items in the queue in the order they were created, along with the names of the
+    /// claimed items and the etcd index the queue was listed at.
+    async fn list(&self) -> Result<(Vec<Node>, HashSet<String>, u64), Vec<Error>> {
+        let options = GetOptions {
+            recursive: true,
+            sort: true,
+            ..Default::default()
+        };
+
+        let (node, index) = match kv::get(&self.client, &self.dir, options).await {
+            Ok(response) => (
+                response.data.node,
+                response.cluster_info.etcd_index.unwrap_or_default(),
+            ),
+            Err(errors) => match key_not_found_index(&errors) {
+                Some(index) => return Ok((Vec::new(), HashSet::new(), index)),
+                None => return Err(errors),
+            },
+        };
+
+        let items_dir = self.items_dir();
+        let inflight_dir = self.inflight_dir();
+        let mut items = Vec::new();
+        let mut claimed = HashSet::new();
+
+        for child in node.nodes.unwrap_or_default() {
+            if child.key.as_ref() == Some(&items_dir) {
+                items = child.nodes.unwrap_or_default();
+            } else if child.key.as_ref() == Some(&inflight_dir) {
+                claimed = child
+                    .nodes
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|node| name(node).to_owned())
+                    .collect();
+            }
+        }
+
+        items.sort_by_key(|node| node.created_index);
+        Ok((items, claimed, index))
+    }
+
+    /// Tries to claim an item, returning `None` if another consumer claimed it first.
+    async fn claim(&self, node: Node) -> Result<Option<Item>, Vec<Error>> {
+        let key = node
+            .key
+            .clone()
+            .expect("invariant: listed nodes should always have a key");
+        let value = node.value.clone().unwrap_or_default();
+
+        let visibility_timeout = match self.visibility_timeout {
+            Some(visibility_timeout) => visibility_timeout,
+            None => {
+                return match kv::compare_and_delete(&self.client, &key, None, node.modified_index)
+                    .await
+                {
+                    Ok(_) => Ok(Some(Item {
+                        key,
+                        value,
+                        claim: None,
+                    })),
+                    Err(ref errors) if is_key_not_found(errors) || is_compare_failed(errors) => {
+                        Ok(None)
+                    }
+                    Err(errors) => Err(errors),
+                };
+            }
+        };
+
+        let claim_key = format!("{}/{}", self.inflight_dir(), name(&node));
+        let claim_index =
+            match kv::create(&self.client, &claim_key, &value, Some(visibility_timeout)).await {
+                Ok(response) => response.data.node.modified_index.unwrap_or_default(),
+                Err(ref errors) if is_key_already_exists(errors) => return Ok(None),
+                Err(errors) => return Err(errors),
+            };
+
+        // The item may have been acked by another consumer between being listed and being claimed.
+        match kv::get(&self.client, &key, GetOptions::default()).await {
+            Ok(_) => Ok(Some(Item {
+                key,
+                value,
+                claim: Some((claim_key, claim_index)),
+            })),
+            Err(ref errors) if is_key_not_found(errors) => {
+                kv::compare_and_delete(&self.client, &claim_key, None, Some(claim_index))
+                    .await
+                    .ok();
+                Ok(None)
+            }
+            Err(errors) => Err(errors),
+        }
+    }
+
+    /// Returns the directory holding the items in the queue.
+    fn items_dir(&self) -> String {
+        format!("{}/items", self.dir)
+    }
+
+    /// Returns the directory holding the claims on items in visibility-timeout mode.
+    fn inflight_dir(&self) -> String {
+        format!("{}/inflight", self.dir)
+    }
+}
+
+impl Item {
+    /// Returns the key of the item in the queue.
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Returns the value of the item.
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+}
+
+/// Returns the last component of a node's key.
+fn name(node: &Node) -> &str {
+    let key = node.key.as_deref().unwrap_or_default();
+    key.rsplit('/').next().unwrap_or(key)
+}
@@ -0,0 +1,166 @@
+//! A distributed read-write lock.
+//!
+//! Each process trying to take the lock creates a key in the lock directory with
+//! `kv::create_in_order`, whose value is either `read` or `write` depending on the kind of access
+//! requested. etcd chooses the names of in-order keys itself, so the kind is stored in the value
+//! rather than as a prefix of the name. Access is granted in the order keys were created: a writer
+//! waits for every earlier key to be deleted, and a reader waits only for earlier writers. Keys
+//! have a TTL and are refreshed while held, so the lock is released by etcd if its holder dies.
+
+use crate::client::Client;
+use crate::error::Error;
+use crate::kv::Node;
+
+use super::{list_in_order, wait_for_deletion, HeldKey};
+
+/// The value of keys requesting shared access.
+const READ: &str = "read";
+
+/// The value of keys requesting exclusive access.
+const WRITE: &str = "write";
+
+/// A distributed read-write lock backed by an etcd directory.
+#[derive(Clone, Debug)]
+pub struct RwLock {
+    /// The client used to make API calls.
+    client: Client,
+    /// The directory holding one key for each process holding or waiting for the lock.
+    dir: String,
+    /// The TTL of each key, in seconds.
+    ttl: u64,
+}
+
+/// Proof that an `RwLock` is held for reading or writing. The lock is released when the guard is
+/// dropped.
+#[derive(Debug)]
+pub struct RwLockGuard {
+    /// The key owned by this guard.
+    key: HeldKey,
+    /// Whether the guard grants exclusive access.
+    write: bool,
+}
+
+impl RwLock {
+    /// Creates a new read-write lock.
+    ///
+    /// # Parameters
+    ///
+    /// * client: A `Client` to use to make the API calls.
+    /// * dir: The directory used to coordinate the lock. All processes using the same directory
+    ///   contend for the same lock.
+    /// * ttl: The TTL of the key representing each process, in seconds. If a process dies, its hold
+    ///   on the lock will be released after at most this long.
+    pub fn new<K>(client: &Client, dir: K, ttl: u64) -> Self
+    where
+        K: AsRef<str>,
+    {
+        RwLock {
+            client: client.clone(),
+            dir: dir.as_ref().trim_end_matches('/').to_owned(),
+            ttl,
+        }
+    }
+
+    /// Waits until the lock is acquired for shared access.
+    ///
+    /// If the returned future is dropped before it completes, this process stops waiting for the
+    /// lock.
+    ///
+    /// # Errors
+    ///
+    /// Fails if any API call fails.
+    pub async fn read(&self) -> Result<RwLockGuard, Vec<Error>> {
+        self.lock(false).await
+    }
+
+    /// Waits until the lock is acquired for exclusive access.
+    ///
+    /// If the returned future is dropped before it completes, this process stops waiting for the
+    /// lock.
+    ///
+    /// # Errors
+    ///
+    /// Fails if any API call fails.
+    pub async fn write(&self) -> Result<RwLockGuard, Vec<Error>> {
+        self.lock(true).await
+    }
+
+    /// Waits until the lock is acquired for the given kind of access.
+    async fn lock(&self, write: bool) -> Result<RwLockGuard, Vec<Error>> {
+        let value = if write { WRITE } else { READ };
+
+        loop {
+            let key = HeldKey::create_in_order(&self.client, &self.dir, value, self.ttl).await?;
+            let guard = RwLockGuard { key, write };
+
+            if self.wait_for_turn(&guard).await? {
+                return Ok(guard);
+            }
+        }
+    }
+
+    /// Waits until no key the guard's key conflicts with was created before it.
+    ///
+    /// Returns false if the key disappeared while waiting, in which case it must be created again.
+    async fn wait_for_turn(&self, guard: &RwLockGuard) -> Result<bool, Vec<Error>> {
+        loop {
+            let (nodes, _) = list_in_order(&self.client, &self.dir).await?;
+            let position = match nodes
+                .iter()
+                .position(|node| node.key.as_deref() == Some(guard.key()))
+            {
+                Some(position) => position,
+                None => return Ok(false),
+            };
+
+            let blocker = nodes[..position]
+                .iter()
+                .rev()
+                .find(|node| guard.write || is_write(node));
+
+            match blocker {
+                Some(node) => wait_for_deletion(&self.client, node).await?,
+                None => return Ok(true),
+            }
+        }
+    }
+}
+
+impl RwLockGuard {
+    /// Returns the key representing this process in the lock directory.
+    pub fn key(&self) -> &str {
+        self.key.key()
+    }
+
+    /// Returns whether the guard grants exclusive access.
+    pub fn is_write(&self) -> bool {
+        self.write
+    }
+
+    /// Returns whether the lock is still known to be held.
+    ///
+    /// The lock is lost if this process's key could not be refreshed before it expired.
+    pub fn is_held(&self) -> bool {
+        self.key.is_alive()
+    }
+
+    /// Resolves once the lock has been lost because this process's key expired.
+    pub async fn lost(&self) {
+        self.key.lost().await
+    }
+
+    /// Releases the lock, waiting for the key to be deleted.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the key could not be deleted. The lock will still be released once the key
+    /// expires.
+    pub async fn unlock(self) -> Result<(), Vec<Error>> {
+        self.key.release().await
+    }
+}
+
+/// Returns whether a key in the lock directory requests exclusive access.
+fn is_write(node: &Node) -> bool {
+    node.value.as_deref() == Some(WRITE)
+}
@@ -0,0 +1,150 @@
+//! A distributed counting semaphore.
+//!
+//! Each process trying to acquire a permit creates a key in the semaphore directory with
+//! `kv::create_in_order`. The processes whose keys are among the first `limit` keys created hold a
+//! permit. Every other process watches the directory and checks its rank again once something in
+//! it changes. Keys have a TTL and are refreshed while held, so a permit is released by etcd if its
+//! holder dies.
+
+use crate::client::Client;
+use crate::error::Error;
+
+use super::{list_in_order, wait_for_change, HeldKey};
+
+/// A distributed counting semaphore backed by an etcd directory.
+#[derive(Clone, Debug)]
+pub struct Semaphore {
+    /// The client used to make API calls.
+    client: Client,
+    /// The directory holding one key for each process holding or waiting for a permit.
+    dir: String,
+    /// The maximum number of permits held at once.
+    limit: usize,
+    /// The TTL of each key, in seconds.
+    ttl: u64,
+}
+
+/// A permit acquired from a `Semaphore`. The permit is released when it is dropped.
+#[derive(Debug)]
+pub struct Permit {
+    /// The key owned by this permit.
+    key: HeldKey,
+}
+
+impl Semaphore {
+    /// Creates a new semaphore.
+    ///
+    /// # Parameters
+    ///
+    /// * client: A `Client` to use to make the API calls.
+    /// * dir: The directory used to coordinate the semaphore. All processes using the same
+    ///   directory contend for the same permits.
+    /// * limit: The maximum number of processes that can hold a permit at once. All processes using
+    ///   the same directory should use the same limit.
+    /// * ttl: The TTL of the key representing each process, in seconds. If a process dies, its
+    ///   permit will be released after at most this long.
+    pub fn new<K>(client: &Client, dir: K, limit: usize, ttl: u64) -> Self
+    where
+        K: AsRef<str>,
+    {
+        Semaphore {
+            client: client.clone(),
+            dir: dir.as_ref().trim_end_matches('/').to_owned(),
+            limit,
+            ttl,
+        }
+    }
+
+    /// Waits until a permit is acquired from the semaphore backed by `dir`.
+    ///
+    /// This is a shorthand for `Semaphore::new(client, dir, limit, ttl).acquire_permit()`, for
+    /// processes that acquire a single permit.
+    ///
+    /// # Parameters
+    ///
+    /// See `Semaphore::new`.
+    ///
+    /// # Errors
+    ///
+    /// Fails if any API call fails.
+    pub async fn acquire<K>(
+        client: &Client,
+        dir: K,
+        limit: usize,
+        ttl: u64,
+    ) -> Result<Permit, Vec<Error>>
+    where
+        K: AsRef<str>,
+    {
+        Semaphore::new(client, dir, limit, ttl)
+            .acquire_permit()
+            .await
+    }
+
+    /// Waits until a permit is acquired.
+    ///
+    /// If the returned future is dropped before it completes, this process stops waiting for a
+    /// permit.
+    ///
+    /// # Errors
+    ///
+    /// Fails if any API call fails.
+    pub async fn acquire_permit(&self) -> Result<Permit, Vec<Error>> {
+        loop {
+            let key = HeldKey::create_in_order(&self.client, &self.dir, "", self.ttl).await?;
+            let permit = Permit { key };
+
+            if self.wait_for_turn(&permit).await? {
+                return Ok(permit);
+            }
+        }
+    }
+
+    /// Waits until the permit's key is among the `limit` oldest in the semaphore directory.
+    ///
+    /// Returns false if the key disappeared while waiting, in which case it must be created again.
+    async fn wait_for_turn(&self, permit: &Permit) -> Result<bool, Vec<Error>> {
+        loop {
+            let (nodes, index) = list_in_order(&self.client, &self.dir).await?;
+            match nodes
+                .iter()
+                .position(|node| node.key.as_deref() == Some(permit.key()))
+            {
+                Some(position) if position < self.limit => return Ok(true),
+                Some(_) => {}
+                None => return Ok(false),
+            }
+
+            wait_for_change(&self.client, &self.dir, index).await?;
+        }
+    }
+}
+
+impl Permit {
+    /// Returns the key representing this process in the semaphore directory.
+    pub fn key(&self) -> &str {
+        self.key.key()
+    }
+
+    /// Returns whether the permit is still known to be held.
+    ///
+    /// The permit is lost if this process's key could not be refreshed before it expired.
+    pub fn is_held(&self) -> bool {
+        self.key.is_alive()
+    }
+
+    /// Resolves once the permit has been lost because this process's key expired.
+    pub async fn lost(&self) {
+        self.key.lost().await
+    }
+
+    /// Releases the permit, waiting for the key to be deleted.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the key could not be deleted. The permit will still be released once the key
+    /// expires.
+    pub async fn release(self) -> Result<(), Vec<Error>> {
+        self.key.release().await
+    }
+}
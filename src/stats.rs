@@ -153,6 +153,10 @@ pub struct StoreStats {
 /// Returns statistics about the leader member of a cluster.
 ///
 /// Fails if JSON decoding fails, which suggests a bug in our schema.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(name = "etcd.stats.leader_stats", skip_all)
+)]
 pub async fn leader_stats(client: &Client) -> Result<Response<LeaderStats>, Error> {
     client.request_first_ok("v2/stats/leader").await
 }
@@ -162,6 +166,10 @@ type VecResultResponse<T> = Vec<Result<Response<T>, Error>>;
 /// Returns statistics about each cluster member the client was initialized with.
 ///
 /// Fails if JSON decoding fails, which suggests a bug in our schema.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(name = "etcd.stats.self_stats", skip_all)
+)]
 pub async fn self_stats(client: &Client) -> VecResultResponse<SelfStats> {
     client.request_on_each_endpoint("v2/stats/self").await
 }
@@ -170,6 +178,10 @@ pub async fn self_stats(client: &Client) -> VecResultResponse<SelfStats> {
 /// with.
 ///
 /// Fails if JSON decoding fails, which suggests a bug in our schema.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(name = "etcd.stats.store_stats", skip_all)
+)]
 pub async fn store_stats(client: &Client) -> VecResultResponse<StoreStats> {
     client.request_on_each_endpoint("v2/stats/store").await
 }
@@ -15,8 +15,8 @@ fn auth() {
 
     // Check that auth is disabled first.
     {
-        let response = test_client.run(|c| auth::status(c)).unwrap();
-        assert_eq!(response.data, false);
+        let response = test_client.run(auth::status).unwrap();
+        assert!(!response.data);
     }
 
     // Create a new user.
@@ -31,7 +31,7 @@ fn auth() {
 
     // Enable auth:
     {
-        let response = test_client.run(|c| auth::enable(c)).unwrap();
+        let response = test_client.run(auth::enable).unwrap();
         assert_eq!(response.data, AuthChange::Changed);
     }
 
@@ -82,7 +82,7 @@ fn auth() {
     // Read the role back:
     {
         let response = test_client
-            .run(|_| auth::get_role(&&authed_client, "rkt"))
+            .run(|_| auth::get_role(&authed_client, "rkt"))
             .unwrap();
         let role = response.data;
         assert!(role.kv_read_permissions().contains(&"/rkt/*".to_owned()));
@@ -116,7 +116,7 @@ fn auth() {
 
     // Check that auth is disabled, using unauthorized client:
     {
-        let response = test_client.run(|c| auth::status(c)).unwrap();
-        assert_eq!(response.data, false);
+        let response = test_client.run(auth::status).unwrap();
+        assert!(!response.data);
     }
 }
@@ -0,0 +1,131 @@
+use std::time::Duration;
+
+use etcd::recipes::barrier::{Barrier, DoubleBarrier};
+use tokio::time::timeout;
+
+use crate::test::{Contender, Partition, TestClient};
+
+mod test;
+
+#[test]
+fn barrier() {
+    let client = TestClient::new();
+
+    client.run(|c| async move {
+        let mut barrier = Barrier::new(c, "/test/barrier", 10);
+        barrier.wait().await.unwrap();
+
+        barrier.hold().await.unwrap();
+        let waiter = Barrier::new(c, "/test/barrier", 10);
+        let waiting = Contender::spawn(async move { waiter.wait().await.unwrap() }).await;
+
+        barrier.release().await.unwrap();
+        waiting.unblocked().await;
+    });
+}
+
+#[test]
+fn double_barrier() {
+    let client = TestClient::new();
+
+    client.run(|c| async move {
+        let mut first = DoubleBarrier::new(c, "/test/double_barrier", 2, 10);
+        let mut second = DoubleBarrier::new(c, "/test/double_barrier", 2, 10);
+
+        let entering = Contender::spawn(async move {
+            first.enter().await.unwrap();
+            first
+        })
+        .await;
+
+        second.enter().await.unwrap();
+        let mut first = entering.unblocked().await;
+
+        let leaving = Contender::spawn(async move {
+            first.leave().await.unwrap();
+        })
+        .await;
+
+        second.leave().await.unwrap();
+        leaving.unblocked().await;
+    });
+}
+
+#[test]
+fn double_barrier_participant_dies_before_entry() {
+    let client = TestClient::new();
+
+    client.run(|c| async move {
+        // A participant whose key is never refreshed, as if its process had died.
+        etcd::kv::create_in_order(c, "/test/double_barrier/waiters", "", Some(1))
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_secs(2)).await;
+
+        let mut first = DoubleBarrier::new(c, "/test/double_barrier", 2, 10);
+        let entering = Contender::spawn(async move {
+            first.enter().await.unwrap();
+            first
+        })
+        .await;
+
+        let mut second = DoubleBarrier::new(c, "/test/double_barrier", 2, 10);
+        second.enter().await.unwrap();
+        entering.unblocked().await;
+    });
+}
+
+#[test]
+fn double_barrier_participants_die_inside() {
+    let client = TestClient::new();
+
+    client.run(|c| async move {
+        let (partitioned, partition) = Partition::client();
+        let mut first = DoubleBarrier::new(&partitioned, "/test/double_barrier", 2, 1);
+        let mut second = DoubleBarrier::new(&partitioned, "/test/double_barrier", 2, 1);
+        let entering = tokio::spawn(async move {
+            first.enter().await.unwrap();
+            first
+        });
+        second.enter().await.unwrap();
+        let first = timeout(Duration::from_secs(5), entering)
+            .await
+            .unwrap()
+            .unwrap();
+
+        // Both participants die without leaving, so all of their keys expire.
+        partition.set(true);
+        drop((first, second));
+        tokio::time::sleep(Duration::from_secs(3)).await;
+
+        let mut third = DoubleBarrier::new(c, "/test/double_barrier", 2, 10);
+        let entering = Contender::spawn(async move {
+            third.enter().await.unwrap();
+            third
+        })
+        .await;
+
+        let mut fourth = DoubleBarrier::new(c, "/test/double_barrier", 2, 10);
+        fourth.enter().await.unwrap();
+        entering.unblocked().await;
+    });
+}
+
+#[test]
+fn double_barrier_participant_dies_before_leaving() {
+    let client = TestClient::new();
+
+    client.run(|c| async move {
+        // A participant whose key is never refreshed, as if its process had died.
+        etcd::kv::create_in_order(c, "/test/double_barrier/waiters", "", Some(2))
+            .await
+            .unwrap();
+
+        let mut barrier = DoubleBarrier::new(c, "/test/double_barrier", 2, 10);
+        barrier.enter().await.unwrap();
+        timeout(Duration::from_secs(5), barrier.leave())
+            .await
+            .unwrap()
+            .unwrap();
+    });
+}
@@ -1,4 +1,27 @@
-use crate::test::TestClient;
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+#[cfg(any(feature = "tls", feature = "rustls-tls"))]
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+#[cfg(any(feature = "tls", feature = "rustls-tls"))]
+use std::{env, fs, process};
+
+use bytes::Bytes;
+use etcd::kv::{self, WatchOptions};
+use etcd::{
+    members, CancellationToken, Client, ClientBuilder, Error, HttpRequest, HttpResponse,
+    InterceptedRequest, Interceptor, MetricsSink, RequestMetrics, RequestOptions, RequestOutcome,
+    ResponseBody, RetryPolicy, Transport,
+};
+use futures::future::{self, BoxFuture, FutureExt};
+use http::header::{HeaderMap, HeaderName, HeaderValue};
+use http::{Method, StatusCode, Uri};
+
+use crate::test::{unresponsive_server, RequestLog, TestClient};
 
 mod test;
 
@@ -23,3 +46,790 @@ fn versions() {
         assert_eq!(response.data.server_version, "2.3.8");
     }
 }
+
+#[test]
+fn failing_endpoint_is_ejected() {
+    let builder = ClientBuilder::new(&["http://127.0.0.1:1", "http://etcd:2379"])
+        .with_endpoint_ejection(1, Duration::from_secs(60));
+    let client = TestClient::from_builder(builder);
+
+    client.run(|c| async move {
+        let dead_health = || {
+            c.endpoint_health()
+                .into_iter()
+                .find(|health| health.endpoint.port_u16() == Some(1))
+                .unwrap()
+        };
+
+        // Endpoints are tried in random order until one of them has failed.
+        for _ in 0..100 {
+            if dead_health().consecutive_failures > 0 {
+                break;
+            }
+            kv::set(c, "/test/foo", "bar", None).await.unwrap();
+        }
+        assert_eq!(dead_health().consecutive_failures, 1);
+        assert!(dead_health().ejected_at.is_some());
+
+        // The ejected endpoint is no longer tried.
+        for _ in 0..10 {
+            kv::set(c, "/test/foo", "bar", None).await.unwrap();
+        }
+        assert_eq!(dead_health().consecutive_failures, 1);
+
+        let alive = c
+            .endpoint_health()
+            .into_iter()
+            .find(|health| health.endpoint.port_u16() == Some(2379))
+            .unwrap();
+        assert_eq!(alive.consecutive_failures, 0);
+        assert_eq!(alive.ejected_at, None);
+    });
+}
+
+/// An interceptor that sends requests for the `recovering` host to etcd once it is up, and to an
+/// unreachable endpoint until then, recording the path of each request made to the host.
+#[derive(Debug, Default)]
+struct Recovering {
+    up: AtomicBool,
+    paths: Mutex<Vec<String>>,
+}
+
+impl Interceptor for Recovering {
+    fn before_request(&self, request: &mut InterceptedRequest<'_>) {
+        if request.url().host_str() != Some("recovering") {
+            return;
+        }
+        self.paths
+            .lock()
+            .unwrap()
+            .push(request.url().path().to_owned());
+
+        let mut url = request.url().clone();
+        if self.up.load(Ordering::SeqCst) {
+            url.set_host(Some("etcd")).unwrap();
+        } else {
+            url.set_host(Some("127.0.0.1")).unwrap();
+            url.set_port(Some(1)).unwrap();
+        }
+        request.set_url(url);
+    }
+}
+
+#[test]
+fn ejected_endpoint_is_probed_in_the_background() {
+    let recovering = Arc::new(Recovering::default());
+    let builder = ClientBuilder::new(&["http://recovering:2379", "http://etcd:2379"])
+        .with_endpoint_ejection(1, Duration::from_millis(200))
+        .with_interceptor(recovering.clone());
+    let client = TestClient::from_builder(builder);
+
+    client.run(|c| async move {
+        let recovering_health = || c.endpoint_health()[0].clone();
+
+        for _ in 0..100 {
+            if recovering_health().consecutive_failures > 0 {
+                break;
+            }
+            kv::set(c, "/test/foo", "bar", None).await.unwrap();
+        }
+        assert!(recovering_health().ejected_at.is_some());
+
+        recovering.up.store(true, Ordering::SeqCst);
+        recovering.paths.lock().unwrap().clear();
+        tokio::time::sleep(Duration::from_millis(300)).await;
+
+        // The request after the cooldown starts the probe, but is not sent to the endpoint itself.
+        kv::set(c, "/test/foo", "bar", None).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        assert_eq!(
+            *recovering.paths.lock().unwrap(),
+            vec!["/health".to_owned()]
+        );
+        assert_eq!(recovering_health().consecutive_failures, 0);
+        assert_eq!(recovering_health().ejected_at, None);
+    });
+}
+
+/// Returns the client URLs of the cluster's members, as listed by a separate client.
+async fn member_client_urls() -> Vec<Uri> {
+    let client = Client::new(&["http://etcd:2379"]);
+    members::list(&client)
+        .await
+        .unwrap()
+        .data
+        .iter()
+        .flat_map(|member| member.client_urls.iter())
+        .map(|url| url.parse().unwrap())
+        .collect()
+}
+
+#[test]
+fn sync_endpoints() {
+    let builder = ClientBuilder::new(&["http://127.0.0.1:1", "http://etcd:2379"]);
+    let client = TestClient::from_builder(builder);
+
+    client.run(|c| async move {
+        let client_urls = member_client_urls().await;
+        assert!(!client_urls.is_empty());
+
+        c.sync_endpoints().await.unwrap();
+        assert_eq!(c.endpoints(), client_urls);
+    });
+}
+
+#[test]
+fn auto_sync_endpoints() {
+    let builder = ClientBuilder::new(&["http://127.0.0.1:1", "http://etcd:2379"])
+        .with_auto_sync(Duration::from_millis(100));
+    let client = TestClient::from_builder(builder);
+
+    client.run(|c| async move {
+        let client_urls = member_client_urls().await;
+        tokio::time::sleep(Duration::from_millis(500)).await;
+
+        assert_eq!(c.endpoints(), client_urls);
+    });
+}
+
+fn retrying_client(endpoints: &[&str]) -> TestClient {
+    let retry_policy =
+        RetryPolicy::new(3).with_backoff(Duration::from_millis(200), Duration::from_millis(200));
+    let builder = ClientBuilder::new(endpoints)
+        .with_endpoint_ejection(u32::MAX, Duration::from_secs(60))
+        .with_retry_policy(retry_policy);
+    TestClient::from_builder(builder)
+}
+
+#[test]
+fn retry_policy_retries_connection_errors() {
+    let client = retrying_client(&["http://127.0.0.1:1"]);
+
+    client.run(|c| async move {
+        assert!(kv::get(c, "/test/foo", Default::default()).await.is_err());
+        assert_eq!(c.endpoint_health()[0].consecutive_failures, 3);
+    });
+}
+
+#[test]
+fn retry_policy_does_not_retry_create_in_order() {
+    let client = retrying_client(&["http://127.0.0.1:1"]);
+
+    client.run(|c| async move {
+        assert!(kv::create_in_order(c, "/test/foo", "bar", None)
+            .await
+            .is_err());
+        assert_eq!(c.endpoint_health()[0].consecutive_failures, 1);
+    });
+}
+
+#[test]
+fn retry_policy_does_not_retry_api_errors() {
+    let log = Arc::new(RequestLog::default());
+    let builder = ClientBuilder::new(&["http://etcd:2379"])
+        .with_retry_policy(RetryPolicy::new(3))
+        .with_interceptor(log.clone());
+    let client = TestClient::from_builder(builder);
+
+    client.run(|c| async move {
+        assert!(kv::get(c, "/test/missing", Default::default())
+            .await
+            .is_err());
+    });
+
+    assert_eq!(log.urls().len(), 1);
+}
+
+/// A transport that fakes a cluster of two members, `leader` and `follower`, recording the host
+/// and path of every request it receives.
+///
+/// Statistics requests are answered after a short delay, so that concurrent requests can overlap
+/// while the leader is being looked up. As with etcd, the raft term is only reported on responses from the keys API.
+#[derive(Debug, Default)]
+struct FakeCluster {
+    requests: Mutex<Vec<(Method, String, String)>>,
+}
+
+impl FakeCluster {
+    fn requests_to(&self, path: &str) -> Vec<(Method, String)> {
+        self.requests
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, _, request_path)| request_path == path)
+            .map(|(method, host, _)| (method.clone(), host.clone()))
+            .collect()
+    }
+}
+
+impl Transport for FakeCluster {
+    fn send(&self, request: HttpRequest) -> BoxFuture<'_, Result<HttpResponse, Error>> {
+        let host = request.url.host_str().unwrap().to_owned();
+        let path = request.url.path().to_owned();
+        self.requests
+            .lock()
+            .unwrap()
+            .push((request.method.clone(), host.clone(), path.clone()));
+
+        let mut headers = HeaderMap::new();
+        let (status, body) = match &path[..] {
+            "/v2/stats/self" => {
+                let state = if host == "leader" {
+                    "StateLeader"
+                } else {
+                    "StateFollower"
+                };
+                let body = format!(
+                    r#"{{"id":"{}","name":"{}","state":"{}","startTime":"","leaderInfo":{{"leader":"leader","startTime":"","uptime":""}},"recvAppendRequestCnt":0,"sendAppendRequestCnt":0}}"#,
+                    host, host, state
+                );
+                (StatusCode::OK, body)
+            }
+            "/v2/members" => (
+                StatusCode::OK,
+                r#"{"members":[{"id":"leader","name":"leader","peerURLs":[],"clientURLs":["http://leader:2379"]},{"id":"follower","name":"follower","peerURLs":[],"clientURLs":["http://follower:2379"]}]}"#
+                    .to_owned(),
+            ),
+            _ => {
+                headers.insert("x-raft-term", HeaderValue::from_static("2"));
+                let action = if request.method == Method::DELETE {
+                    "delete"
+                } else {
+                    "set"
+                };
+                let body = format!(
+                    r#"{{"action":"{}","node":{{"key":"/test/foo","value":"bar","modifiedIndex":7,"createdIndex":7}}}}"#,
+                    action
+                );
+                (StatusCode::OK, body)
+            }
+        };
+
+        let delay = if path.starts_with("/v2/stats/") {
+            Duration::from_millis(50)
+        } else {
+            Duration::from_millis(0)
+        };
+        async move {
+            tokio::time::sleep(delay).await;
+            Ok(HttpResponse {
+                status,
+                headers,
+                body: body.into(),
+            })
+        }
+        .boxed()
+    }
+}
+
+#[test]
+fn leader_routing_sends_writes_to_leader() {
+    let cluster = Arc::new(FakeCluster::default());
+    let builder = ClientBuilder::new(&["http://follower:2379", "http://leader:2379"])
+        .with_transport(cluster.clone())
+        .with_leader_routing();
+    let client = TestClient::from_builder(builder);
+
+    client.run(|c| async move {
+        for _ in 0..10 {
+            kv::set(c, "/test/foo", "bar", None).await.unwrap();
+            kv::delete(c, "/test/foo", false).await.unwrap();
+        }
+    });
+
+    let writes = cluster.requests_to("/v2/keys/test/foo");
+    assert_eq!(writes.len(), 20);
+    assert!(writes.iter().all(|(_, host)| host == "leader"));
+
+    // The leader is only looked up once, as the raft term stays the same.
+    assert_eq!(cluster.requests_to("/v2/stats/self").len(), 2);
+    assert_eq!(cluster.requests_to("/v2/members").len(), 1);
+}
+
+#[test]
+fn leader_routing_looks_up_leader_once_for_concurrent_writes() {
+    let cluster = Arc::new(FakeCluster::default());
+    let builder = ClientBuilder::new(&["http://follower:2379", "http://leader:2379"])
+        .with_transport(cluster.clone())
+        .with_leader_routing();
+    let client = TestClient::from_builder(builder);
+
+    client.run(|c| async move {
+        let writes = (0..10).map(|_| kv::set(c, "/test/foo", "bar", None));
+        for result in future::join_all(writes).await {
+            result.unwrap();
+        }
+    });
+
+    let writes = cluster.requests_to("/v2/keys/test/foo");
+    assert_eq!(writes.len(), 10);
+    assert!(writes.iter().all(|(_, host)| host == "leader"));
+
+    // The writes wait for a single lookup rather than each looking up the leader.
+    assert_eq!(cluster.requests_to("/v2/stats/self").len(), 2);
+    assert_eq!(cluster.requests_to("/v2/members").len(), 1);
+}
+
+#[test]
+fn request_options_timeout() {
+    let (endpoint, _requests) = unresponsive_server();
+    let builder = ClientBuilder::new(&[&endpoint]).with_request_timeout(Duration::from_secs(1));
+    let client = TestClient::from_builder(builder);
+
+    client.run(|c| async move {
+        let c =
+            c.with_request_options(RequestOptions::new().with_timeout(Duration::from_millis(200)));
+        let start = Instant::now();
+        let errors = kv::get(&c, "/test/foo", Default::default())
+            .await
+            .unwrap_err();
+        assert!(start.elapsed() < Duration::from_millis(800));
+        assert!(matches!(errors[0], Error::Http(ref error) if error.is_timeout()));
+    });
+}
+
+#[test]
+fn request_options_deadline() {
+    let (endpoint, _requests) = unresponsive_server();
+    let (other_endpoint, _other_requests) = unresponsive_server();
+    let builder = ClientBuilder::new(&[&endpoint, &other_endpoint])
+        .with_request_timeout(Duration::from_secs(1));
+    let client = TestClient::from_builder(builder);
+
+    client.run(|c| async move {
+        let deadline = Instant::now() + Duration::from_millis(200);
+        let c = c.with_request_options(RequestOptions::new().with_deadline(deadline));
+        let errors = kv::get(&c, "/test/foo", Default::default())
+            .await
+            .unwrap_err();
+        assert!(Instant::now() < deadline + Duration::from_secs(1));
+        assert!(matches!(errors[..], [Error::DeadlineExceeded]));
+    });
+}
+
+#[test]
+fn request_options_cancellation() {
+    let client = TestClient::new();
+
+    client.run(|c| async move {
+        let cancellation = CancellationToken::new();
+        let c =
+            c.with_request_options(RequestOptions::new().with_cancellation(cancellation.clone()));
+
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            cancellation.cancel();
+        });
+
+        match kv::watch(&c, "/test/foo", WatchOptions::default()).await {
+            Err(kv::WatchError::Other(errors)) => {
+                assert!(matches!(errors[..], [Error::Cancelled]))
+            }
+            result => panic!("expected the watch to be cancelled, got {:?}", result),
+        }
+    });
+}
+
+#[test]
+fn request_options_headers() {
+    let (endpoint, requests) = unresponsive_server();
+    let builder = ClientBuilder::new(&[&endpoint]).with_request_timeout(Duration::from_secs(1));
+    let client = TestClient::from_builder(builder);
+
+    client.run(|c| async move {
+        let options = RequestOptions::new()
+            .with_timeout(Duration::from_millis(200))
+            .with_header(
+                HeaderName::from_static("x-request-id"),
+                HeaderValue::from_static("test"),
+            );
+        let c = c.with_request_options(options);
+        assert!(kv::get(&c, "/test/foo", Default::default()).await.is_err());
+    });
+
+    let request = requests.recv_timeout(Duration::from_secs(1)).unwrap();
+    assert!(request.to_lowercase().contains("x-request-id: test"));
+}
+
+/// An interceptor that sends requests for an unreachable endpoint to a working one instead, and
+/// records the outcome of each request.
+#[derive(Debug, Default)]
+struct RewritingInterceptor {
+    outcomes: Mutex<Vec<(Method, String, Option<StatusCode>)>>,
+}
+
+impl Interceptor for RewritingInterceptor {
+    fn before_request(&self, request: &mut InterceptedRequest<'_>) {
+        let mut url = request.url().clone();
+        url.set_host(Some("etcd")).unwrap();
+        url.set_port(Some(2379)).unwrap();
+        request.set_url(url);
+    }
+
+    fn after_request(&self, outcome: &RequestOutcome<'_>) {
+        assert!(outcome.cluster_info.is_some());
+        self.outcomes.lock().unwrap().push((
+            outcome.method.clone(),
+            outcome.url.to_string(),
+            outcome.status,
+        ));
+    }
+}
+
+#[test]
+fn interceptor() {
+    let interceptor = Arc::new(RewritingInterceptor::default());
+    let builder = ClientBuilder::new(&["http://127.0.0.1:1"]).with_interceptor(interceptor.clone());
+    let client = TestClient::from_builder(builder);
+
+    client.run(|c| async move {
+        kv::set(c, "/test/foo", "bar", None).await.unwrap();
+        kv::get(c, "/test/foo", Default::default()).await.unwrap();
+    });
+
+    let outcomes = interceptor.outcomes.lock().unwrap();
+    assert_eq!(
+        outcomes[..2],
+        [
+            (
+                Method::PUT,
+                "http://etcd:2379/v2/keys/test/foo".to_owned(),
+                Some(StatusCode::CREATED)
+            ),
+            (
+                Method::GET,
+                "http://etcd:2379/v2/keys/test/foo?recursive=false&sorted=false".to_owned(),
+                Some(StatusCode::OK)
+            ),
+        ]
+    );
+}
+
+/// A metrics sink that keeps everything it records.
+#[derive(Debug, Default)]
+struct RecordingSink {
+    requests: Mutex<Vec<(String, String, Option<StatusCode>)>>,
+    elapsed: Mutex<Vec<Duration>>,
+    errors: Mutex<Vec<String>>,
+    failovers: Mutex<Vec<Uri>>,
+    open_watches: Mutex<Vec<usize>>,
+}
+
+impl MetricsSink for RecordingSink {
+    fn record_request(&self, request: &RequestMetrics<'_>) {
+        self.requests.lock().unwrap().push((
+            request.endpoint.to_owned(),
+            request.api.to_owned(),
+            request.status,
+        ));
+        self.elapsed.lock().unwrap().push(request.elapsed);
+    }
+
+    fn record_error(&self, _endpoint: &Uri, error: &Error) {
+        self.errors.lock().unwrap().push(error.to_string());
+    }
+
+    fn record_failover(&self, endpoint: &Uri) {
+        self.failovers.lock().unwrap().push(endpoint.clone());
+    }
+
+    fn record_open_watches(&self, open_watches: usize) {
+        self.open_watches.lock().unwrap().push(open_watches);
+    }
+}
+
+#[test]
+fn metrics_sink_records_failovers() {
+    let sink = Arc::new(RecordingSink::default());
+    let builder = ClientBuilder::new(&["http://127.0.0.1:1", "http://127.0.0.1:2"])
+        .with_metrics_sink(sink.clone());
+    let client = TestClient::from_builder(builder);
+
+    client.run(|c| async move {
+        assert!(kv::get(c, "/test/foo", Default::default()).await.is_err());
+    });
+
+    let mut requests = sink.requests.lock().unwrap().clone();
+    requests.sort_by(|a, b| a.0.cmp(&b.0));
+    assert_eq!(
+        requests[..2],
+        [
+            ("http://127.0.0.1:1".to_owned(), "keys".to_owned(), None),
+            ("http://127.0.0.1:2".to_owned(), "keys".to_owned(), None),
+        ]
+    );
+    assert_eq!(sink.errors.lock().unwrap().len(), 2);
+    assert_eq!(sink.failovers.lock().unwrap().len(), 1);
+}
+
+#[test]
+fn metrics_sink_records_open_watches() {
+    let sink = Arc::new(RecordingSink::default());
+    let builder = ClientBuilder::new(&["http://etcd:2379"]).with_metrics_sink(sink.clone());
+    let client = TestClient::from_builder(builder);
+
+    client.run(|c| async move {
+        let options = WatchOptions {
+            timeout: Some(Duration::from_millis(200)),
+            ..Default::default()
+        };
+        assert!(kv::watch(c, "/test/foo", options).await.is_err());
+    });
+
+    assert_eq!(*sink.open_watches.lock().unwrap(), [1, 0]);
+}
+
+#[cfg(any(feature = "tls", feature = "rustls-tls"))]
+#[test]
+fn tls_files_missing() {
+    let result = ClientBuilder::new(&["https://etcdsecure:2379"])
+        .with_tls_files(
+            "/nonexistent/ca.pem",
+            "/nonexistent/client.pem",
+            "/nonexistent/client-key.pem",
+        )
+        .try_build();
+
+    assert!(matches!(result, Err(Error::TlsFiles(_))));
+}
+
+#[cfg(any(feature = "tls", feature = "rustls-tls"))]
+#[test]
+fn tls_files_reload() {
+    // The client key must be in PKCS #8 format for native TLS, which the Makefile generates.
+    let ssl = Path::new("/source/tests/ssl");
+    let dir = env::temp_dir().join(format!("etcd-tls-files-{}", process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    for name in &["client.pem", "client-key.pem"] {
+        fs::copy(ssl.join(name), dir.join(name)).unwrap();
+    }
+    // The client certificate isn't the CA that signed etcd's certificate, so etcd isn't trusted.
+    fs::copy(ssl.join("client.pem"), dir.join("ca.pem")).unwrap();
+
+    let builder = ClientBuilder::new(&["https://etcdsecure:2379"])
+        .with_tls_files(
+            dir.join("ca.pem"),
+            dir.join("client.pem"),
+            dir.join("client-key.pem"),
+        )
+        .with_tls_reload(Duration::from_millis(100));
+    // The files are loaded for whichever backend is chosen, even after they are given.
+    #[cfg(feature = "rustls-tls")]
+    let builder = builder.with_rustls_tls();
+    let client = TestClient::from_builder(builder);
+
+    let errors = client
+        .run(|c| kv::set(c, "/test/foo", "bar", None))
+        .unwrap_err();
+    assert!(matches!(errors[..], [Error::Http(_)]));
+
+    // Replacing the CA makes the client trust etcd once the files are reloaded.
+    fs::copy(ssl.join("ca.pem"), dir.join("ca.pem")).unwrap();
+    thread::sleep(Duration::from_millis(500));
+
+    client
+        .run(|c| kv::set(c, "/test/foo", "bar", None))
+        .unwrap();
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+/// Starts an HTTP proxy that relays every connection to etcd, passing the first request it
+/// receives on each connection to the returned channel.
+fn recording_proxy() -> (String, Receiver<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let proxy = format!("http://{}", listener.local_addr().unwrap());
+    let (sender, receiver) = mpsc::channel();
+
+    thread::spawn(move || {
+        for mut stream in listener.incoming().flatten() {
+            let mut head = Vec::new();
+            let mut byte = [0; 1];
+            while !head.ends_with(b"\r\n\r\n") && stream.read(&mut byte).unwrap_or(0) == 1 {
+                head.push(byte[0]);
+            }
+            sender
+                .send(String::from_utf8_lossy(&head).into_owned())
+                .ok();
+
+            let mut upstream = TcpStream::connect("etcd:2379").unwrap();
+            upstream.write_all(&head).unwrap();
+            let mut downstream = stream.try_clone().unwrap();
+            let mut upstream_reader = upstream.try_clone().unwrap();
+            thread::spawn(move || io::copy(&mut stream, &mut upstream));
+            thread::spawn(move || io::copy(&mut upstream_reader, &mut downstream));
+        }
+    });
+
+    (proxy, receiver)
+}
+
+#[test]
+fn proxy() {
+    let (proxy, requests) = recording_proxy();
+    let builder = ClientBuilder::new(&["http://etcd:2379"])
+        .with_proxy(&proxy)
+        .unwrap()
+        .with_proxy_auth("user", "secret");
+    let client = TestClient::from_builder(builder);
+
+    client.run(|c| async move {
+        let res = kv::set(c, "/test/foo", "bar", None).await.unwrap();
+        let index = res.data.node.modified_index.unwrap();
+
+        let other = c.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            kv::set(&other, "/test/foo", "baz", None).await.unwrap();
+        });
+
+        let options = WatchOptions {
+            index: Some(index + 1),
+            timeout: Some(Duration::from_secs(5)),
+            ..Default::default()
+        };
+        let res = kv::watch(c, "/test/foo", options).await.unwrap();
+        assert_eq!(res.data.node.value.unwrap(), "baz");
+    });
+
+    let request = requests.recv().unwrap().to_lowercase();
+    assert!(request.starts_with("put http://etcd:2379/v2/keys/test/foo "));
+    assert!(request.contains("proxy-authorization: basic dxnlcjpzzwnyzxq=\r\n"));
+}
+
+#[test]
+fn no_proxy() {
+    let (proxy, requests) = recording_proxy();
+    let builder = ClientBuilder::new(&["http://etcd:2379"])
+        .with_proxy(&proxy)
+        .unwrap()
+        .with_no_proxy(&["etcd"]);
+    let client = TestClient::from_builder(builder);
+
+    client
+        .run(|c| kv::set(c, "/test/foo", "bar", None))
+        .unwrap();
+
+    assert!(requests.try_recv().is_err());
+}
+
+/// A transport that answers every request with the same key, failing requests to
+/// `http://down:2379` and taking 500 milliseconds to send the body of responses from
+/// `http://slow:2379`.
+#[derive(Debug, Default)]
+struct FakeTransport {
+    requests: Mutex<Vec<HttpRequest>>,
+}
+
+impl Transport for FakeTransport {
+    fn send(&self, request: HttpRequest) -> BoxFuture<'_, Result<HttpResponse, Error>> {
+        self.requests.lock().unwrap().push(request.clone());
+
+        let body = Bytes::from_static(
+            br#"{"action":"get","node":{"key":"/test/foo","value":"bar","modifiedIndex":7,"createdIndex":7}}"#,
+        );
+        let result = match request.url.host_str() {
+            Some("down") => Err(Error::Transport("connection refused".into())),
+            host => {
+                let mut headers = HeaderMap::new();
+                headers.insert("x-etcd-index", HeaderValue::from_static("7"));
+                let body = if host == Some("slow") {
+                    ResponseBody::from_future(async move {
+                        tokio::time::sleep(Duration::from_millis(500)).await;
+                        Ok(body)
+                    })
+                } else {
+                    body.into()
+                };
+                Ok(HttpResponse {
+                    status: StatusCode::OK,
+                    headers,
+                    body,
+                })
+            }
+        };
+
+        future::ready(result).boxed()
+    }
+}
+
+#[test]
+fn transport() {
+    let transport = Arc::new(FakeTransport::default());
+    let builder = ClientBuilder::new(&["http://fake:2379"])
+        .with_basic_auth("user", "secret")
+        .with_transport(transport.clone());
+    let client = TestClient::from_builder(builder);
+
+    client.run(|c| async move {
+        let res = kv::get(c, "/test/foo", Default::default()).await.unwrap();
+        assert_eq!(res.data.node.value.unwrap(), "bar");
+        assert_eq!(res.cluster_info.etcd_index, Some(7));
+    });
+
+    let request = transport.requests.lock().unwrap()[0].clone();
+    assert_eq!(request.method, Method::GET);
+    assert_eq!(request.url.path(), "/v2/keys/test/foo");
+    assert_eq!(
+        request.headers[http::header::AUTHORIZATION],
+        "Basic dXNlcjpzZWNyZXQ="
+    );
+}
+
+#[test]
+fn transport_errors() {
+    let builder = ClientBuilder::new(&["http://down:2379"])
+        .with_transport(Arc::new(FakeTransport::default()));
+    let client = TestClient::from_builder(builder);
+
+    client.run(|c| async move {
+        let errors = kv::get(c, "/test/foo", Default::default())
+            .await
+            .unwrap_err();
+        assert!(matches!(errors[..], [Error::Transport(_)]));
+    });
+}
+
+#[test]
+fn transport_errors_are_retried() {
+    let transport = Arc::new(FakeTransport::default());
+    let retry_policy =
+        RetryPolicy::new(3).with_backoff(Duration::from_millis(10), Duration::from_millis(10));
+    let builder = ClientBuilder::new(&["http://down:2379"])
+        .with_transport(transport.clone())
+        .with_retry_policy(retry_policy);
+    let client = TestClient::from_builder(builder);
+
+    client.run(|c| async move {
+        assert!(kv::get(c, "/test/foo", Default::default()).await.is_err());
+    });
+
+    let gets = transport
+        .requests
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|request| request.method == Method::GET)
+        .count();
+    assert_eq!(gets, 3);
+}
+
+#[test]
+fn metrics_sink_records_time_to_headers() {
+    let sink = Arc::new(RecordingSink::default());
+    let builder = ClientBuilder::new(&["http://slow:2379"])
+        .with_transport(Arc::new(FakeTransport::default()))
+        .with_metrics_sink(sink.clone());
+    let client = TestClient::from_builder(builder);
+
+    client.run(|c| async move {
+        let start = Instant::now();
+        let res = kv::get(c, "/test/foo", Default::default()).await.unwrap();
+        assert_eq!(res.data.node.value.unwrap(), "bar");
+        assert!(start.elapsed() >= Duration::from_millis(500));
+    });
+
+    // The body took 500 milliseconds to arrive, which isn't counted.
+    assert!(sink.elapsed.lock().unwrap()[0] < Duration::from_millis(250));
+}
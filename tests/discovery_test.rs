@@ -0,0 +1,147 @@
+use std::time::Duration;
+
+use etcd::discovery::{self, DiscoveryEvent, Instance};
+use etcd::Error;
+use futures::StreamExt;
+use serde_derive::{Deserialize, Serialize};
+use tokio::time::timeout;
+
+use crate::test::TestClient;
+
+mod test;
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+struct Metadata {
+    address: String,
+}
+
+fn metadata(address: &str) -> Metadata {
+    Metadata {
+        address: address.to_owned(),
+    }
+}
+
+#[test]
+fn register_and_discover() {
+    let client = TestClient::new();
+
+    client.run(|c| async move {
+        let a = discovery::register(c, "/test/services/api", "a", &metadata("10.0.0.1"), 10)
+            .await
+            .unwrap();
+        assert_eq!(a.key(), "/test/services/api/a");
+
+        let mut discovered = discovery::discover::<_, Metadata>(c, "/test/services/api")
+            .await
+            .unwrap();
+        assert_eq!(
+            discovered.instances,
+            vec![Instance {
+                id: "a".to_owned(),
+                metadata: metadata("10.0.0.1"),
+            }]
+        );
+
+        let b = discovery::register(c, "/test/services/api", "b", &metadata("10.0.0.2"), 10)
+            .await
+            .unwrap();
+        let event = timeout(Duration::from_secs(5), discovered.changes.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            event,
+            DiscoveryEvent::Added(Instance {
+                id: "b".to_owned(),
+                metadata: metadata("10.0.0.2"),
+            })
+        );
+
+        a.shutdown().await.unwrap();
+        let event = timeout(Duration::from_secs(5), discovered.changes.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert_eq!(event, DiscoveryEvent::Removed("a".to_owned()));
+
+        drop(b);
+        let event = timeout(Duration::from_secs(5), discovered.changes.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert_eq!(event, DiscoveryEvent::Removed("b".to_owned()));
+    });
+}
+
+#[test]
+fn expired_instance_is_removed() {
+    let client = TestClient::new();
+
+    client.run(|c| async move {
+        let mut discovered = discovery::discover::<_, Metadata>(c, "/test/services/api")
+            .await
+            .unwrap();
+        assert!(discovered.instances.is_empty());
+
+        etcd::kv::set(
+            c,
+            "/test/services/api/a",
+            r#"{"address":"10.0.0.1"}"#,
+            Some(1),
+        )
+        .await
+        .unwrap();
+        let event = timeout(Duration::from_secs(5), discovered.changes.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert!(matches!(event, DiscoveryEvent::Added(_)));
+
+        let event = timeout(Duration::from_secs(5), discovered.changes.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert_eq!(event, DiscoveryEvent::Removed("a".to_owned()));
+    });
+}
+
+#[test]
+fn register_rejects_zero_ttl() {
+    let client = TestClient::new();
+
+    client.run(|c| async move {
+        let errors = discovery::register(c, "/test/services/api", "a", &metadata("10.0.0.1"), 0)
+            .await
+            .unwrap_err();
+        assert!(matches!(errors.as_slice(), [Error::InvalidTtl]));
+    });
+}
+
+#[test]
+fn deleted_instance_is_registered_again() {
+    let client = TestClient::new();
+
+    client.run(|c| async move {
+        let _registration =
+            discovery::register(c, "/test/services/api", "a", &metadata("10.0.0.1"), 1)
+                .await
+                .unwrap();
+        etcd::kv::delete(c, "/test/services/api/a", false)
+            .await
+            .unwrap();
+
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        let response = etcd::kv::get(c, "/test/services/api/a", Default::default())
+            .await
+            .unwrap();
+        assert_eq!(
+            response.data.node.value.unwrap(),
+            r#"{"address":"10.0.0.1"}"#
+        );
+    });
+}
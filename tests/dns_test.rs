@@ -0,0 +1,266 @@
+use std::io::{Read, Write};
+use std::net::{TcpListener, UdpSocket};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use etcd::dns::DnsResolver;
+use etcd::{kv, ClientBuilder};
+use tokio::runtime::Runtime;
+
+use crate::test::TestClient;
+
+mod test;
+
+/// A stub name server answering SRV queries for `_etcd-client._tcp.example.test` with a single
+/// record pointing to the current target, and every other query with NXDOMAIN.
+///
+/// Queries are answered over both UDP and TCP, and UDP responses can be truncated.
+struct StubNameServer {
+    address: std::net::SocketAddr,
+    target: Arc<Mutex<String>>,
+    truncate: Arc<AtomicBool>,
+}
+
+impl StubNameServer {
+    fn start(target: &str) -> Self {
+        // The TCP port picked for the UDP socket's address may already be taken.
+        let (socket, listener) = loop {
+            let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+            if let Ok(listener) = TcpListener::bind(socket.local_addr().unwrap()) {
+                break (socket, listener);
+            }
+        };
+        let address = socket.local_addr().unwrap();
+        let target = Arc::new(Mutex::new(target.to_owned()));
+        let truncate = Arc::new(AtomicBool::new(false));
+
+        let current_target = target.clone();
+        let truncating = truncate.clone();
+        thread::spawn(move || {
+            let mut buffer = [0; 512];
+            loop {
+                let (length, peer) = socket.recv_from(&mut buffer).unwrap();
+                let target = current_target.lock().unwrap().clone();
+                let mut response = respond(&buffer[..length], &target);
+                if truncating.load(Ordering::SeqCst) {
+                    response = truncated(&response);
+                }
+                socket.send_to(&response, peer).unwrap();
+            }
+        });
+
+        let current_target = target.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut length = [0; 2];
+                stream.read_exact(&mut length).unwrap();
+                let mut query = vec![0; u16::from_be_bytes(length) as usize];
+                stream.read_exact(&mut query).unwrap();
+
+                let target = current_target.lock().unwrap().clone();
+                let response = respond(&query, &target);
+                stream
+                    .write_all(&(response.len() as u16).to_be_bytes())
+                    .unwrap();
+                stream.write_all(&response).unwrap();
+            }
+        });
+
+        StubNameServer {
+            address,
+            target,
+            truncate,
+        }
+    }
+
+    fn set_target(&self, target: &str) {
+        *self.target.lock().unwrap() = target.to_owned();
+    }
+
+    fn set_truncate(&self, truncate: bool) {
+        self.truncate.store(truncate, Ordering::SeqCst);
+    }
+}
+
+/// Returns a response with the TC flag set and its answers removed.
+fn truncated(response: &[u8]) -> Vec<u8> {
+    let mut truncated = response.to_vec();
+    truncated[2] |= 0x02;
+    truncated[6..8].copy_from_slice(&[0, 0]);
+    truncated
+}
+
+fn respond(query: &[u8], target: &str) -> Vec<u8> {
+    // The question starts right after the 12 byte header and ends after its type and class.
+    let mut end = 12;
+    let mut labels = Vec::new();
+    while query[end] != 0 {
+        let length = query[end] as usize;
+        labels.push(String::from_utf8_lossy(&query[end + 1..end + 1 + length]).into_owned());
+        end += 1 + length;
+    }
+    let question = &query[12..end + 5];
+    let found = labels.join(".") == "_etcd-client._tcp.example.test";
+
+    let mut response = Vec::new();
+    response.extend_from_slice(&query[..2]);
+    response.extend_from_slice(if found { &[0x81, 0x80] } else { &[0x81, 0x83] });
+    response.extend_from_slice(&[0, 1, 0, found as u8, 0, 0, 0, 0]);
+    response.extend_from_slice(question);
+
+    if found {
+        let mut rdata = vec![0, 10, 0, 5, 0x09, 0x4b];
+        for label in target.split('.') {
+            rdata.push(label.len() as u8);
+            rdata.extend_from_slice(label.as_bytes());
+        }
+        rdata.push(0);
+
+        // A pointer to the name in the question, then the SRV type, class, TTL and data.
+        response.extend_from_slice(&[0xc0, 0x0c, 0, 33, 0, 1, 0, 0, 0, 60]);
+        response.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+        response.extend_from_slice(&rdata);
+    }
+
+    response
+}
+
+#[test]
+fn from_dns_srv() {
+    let name_server = StubNameServer::start("etcd");
+    let resolver = Arc::new(DnsResolver::new(name_server.address));
+    let builder = Runtime::new()
+        .unwrap()
+        .block_on(ClientBuilder::from_dns_srv_with_resolver(
+            "example.test",
+            resolver,
+        ))
+        .unwrap();
+    let client = TestClient::from_builder(builder);
+
+    client.run(|c| async move {
+        let endpoints = c.endpoints();
+        assert_eq!(endpoints.len(), 1);
+        assert_eq!(endpoints[0].scheme_str(), Some("http"));
+        assert_eq!(endpoints[0].host(), Some("etcd"));
+        assert_eq!(endpoints[0].port_u16(), Some(2379));
+
+        kv::set(c, "/test/foo", "bar", None).await.unwrap();
+    });
+}
+
+#[test]
+fn from_dns_srv_without_records() {
+    let name_server = StubNameServer::start("etcd");
+    let resolver = Arc::new(DnsResolver::new(name_server.address));
+    let result = Runtime::new()
+        .unwrap()
+        .block_on(ClientBuilder::from_dns_srv_with_resolver(
+            "missing.test",
+            resolver,
+        ));
+
+    assert!(result.is_err());
+}
+
+#[test]
+fn dns_srv_refresh() {
+    let name_server = StubNameServer::start("etcd");
+    let resolver = Arc::new(DnsResolver::new(name_server.address));
+    let builder = Runtime::new()
+        .unwrap()
+        .block_on(ClientBuilder::from_dns_srv_with_resolver(
+            "example.test",
+            resolver,
+        ))
+        .unwrap()
+        .with_dns_srv_refresh(Duration::from_millis(100));
+    let client = TestClient::from_builder(builder);
+
+    name_server.set_target("etcd.example.test");
+    client.run(|c| async move {
+        tokio::time::sleep(Duration::from_millis(500)).await;
+
+        let endpoints = c.endpoints();
+        assert_eq!(endpoints.len(), 1);
+        assert_eq!(endpoints[0].host(), Some("etcd.example.test"));
+    });
+}
+
+#[test]
+fn from_dns_srv_over_tcp_when_truncated() {
+    let name_server = StubNameServer::start("etcd");
+    name_server.set_truncate(true);
+    let resolver = Arc::new(DnsResolver::new(name_server.address));
+    let builder = Runtime::new()
+        .unwrap()
+        .block_on(ClientBuilder::from_dns_srv_with_resolver(
+            "example.test",
+            resolver,
+        ))
+        .unwrap();
+    let client = TestClient::from_builder(builder);
+
+    client.run(|c| async move {
+        let endpoints = c.endpoints();
+        assert_eq!(endpoints.len(), 1);
+        assert_eq!(endpoints[0].host(), Some("etcd"));
+    });
+}
+
+#[test]
+fn from_dns_srv_falls_back_to_next_name_server() {
+    // A name server that never responds.
+    let silent = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let name_server = StubNameServer::start("etcd");
+    let resolver = Arc::new(
+        DnsResolver::new(silent.local_addr().unwrap())
+            .with_name_server(name_server.address)
+            .with_timeout(Duration::from_millis(200)),
+    );
+    let builder = Runtime::new()
+        .unwrap()
+        .block_on(ClientBuilder::from_dns_srv_with_resolver(
+            "example.test",
+            resolver,
+        ))
+        .unwrap();
+    let client = TestClient::from_builder(builder);
+
+    client.run(|c| async move {
+        let endpoints = c.endpoints();
+        assert_eq!(endpoints.len(), 1);
+        assert_eq!(endpoints[0].host(), Some("etcd"));
+    });
+}
+
+#[test]
+fn from_dns_srv_with_search_domains() {
+    let name_server = StubNameServer::start("etcd");
+    let resolver =
+        Arc::new(DnsResolver::new(name_server.address).with_search_domains(vec!["test"]));
+    let runtime = Runtime::new().unwrap();
+
+    let builder = runtime
+        .block_on(ClientBuilder::from_dns_srv_with_resolver(
+            "example",
+            resolver.clone(),
+        ))
+        .unwrap();
+    let client = TestClient::from_builder(builder);
+    client.run(|c| async move {
+        let endpoints = c.endpoints();
+        assert_eq!(endpoints.len(), 1);
+        assert_eq!(endpoints[0].host(), Some("etcd"));
+    });
+
+    // Fully qualified names are not looked up relative to the search domains.
+    assert!(runtime
+        .block_on(ClientBuilder::from_dns_srv_with_resolver(
+            "example.", resolver,
+        ))
+        .is_err());
+}
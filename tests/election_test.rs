@@ -0,0 +1,63 @@
+use std::time::Duration;
+
+use etcd::recipes::election;
+use futures::StreamExt;
+use tokio::time::timeout;
+
+use crate::test::{Contender, TestClient};
+
+mod test;
+
+#[test]
+fn campaign_and_resign() {
+    let client = TestClient::new();
+
+    client.run(|c| async move {
+        let mut observer = Box::pin(election::observe(c, "/test/election"));
+        assert_eq!(observer.next().await.unwrap().unwrap(), None);
+
+        let mut leader = election::campaign(c, "/test/election", "a", 10)
+            .await
+            .unwrap();
+        assert!(leader.is_leader());
+        assert_eq!(
+            observer.next().await.unwrap().unwrap(),
+            Some("a".to_owned())
+        );
+
+        let contender = c.clone();
+        let campaigning = Contender::spawn(async move {
+            election::campaign(&contender, "/test/election", "b", 10)
+                .await
+                .unwrap()
+        })
+        .await;
+
+        leader.proclaim("a2").await.unwrap();
+        assert_eq!(
+            observer.next().await.unwrap().unwrap(),
+            Some("a2".to_owned())
+        );
+
+        leader.resign().await.unwrap();
+        let next_leader = campaigning.unblocked().await;
+        assert_eq!(next_leader.value(), "b");
+    });
+}
+
+#[test]
+fn leadership_lost() {
+    let client = TestClient::new();
+
+    client.run(|c| async move {
+        let leader = election::campaign(c, "/test/election", "a", 3)
+            .await
+            .unwrap();
+        etcd::kv::delete(c, leader.key(), false).await.unwrap();
+
+        timeout(Duration::from_secs(5), leader.lost())
+            .await
+            .unwrap();
+        assert!(!leader.is_leader());
+    });
+}
@@ -1,9 +1,14 @@
-use std::time::Duration;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use etcd::kv::{self, Action, GetOptions, KeyValueInfo, WatchError, WatchOptions};
-use etcd::Error;
+use futures::StreamExt;
 
-use crate::test::TestClient;
+use etcd::kv::{
+    self, Action, GetOptions, KeyValueInfo, Reflector, WatchError, WatchEvent, WatchOptions,
+};
+use etcd::{Client, ClientBuilder, Error};
+
+use crate::test::{unresponsive_server, Partition, RequestLog, TestClient};
 
 mod test;
 
@@ -52,7 +57,7 @@ fn create_in_order() {
         .collect();
     let results = results.unwrap();
     let mut kvis: Vec<KeyValueInfo> = results.into_iter().map(|response| response.data).collect();
-    kvis.sort_by_key(|ref kvi| kvi.node.modified_index);
+    kvis.sort_by_key(|kvi| kvi.node.modified_index);
 
     let keys: Vec<String> = kvis.into_iter().map(|kvi| kvi.node.key.unwrap()).collect();
 
@@ -64,7 +69,7 @@ fn create_in_order() {
 fn create_in_order_must_operate_on_a_directory() {
     let client = TestClient::new();
     client
-        .run(|c| kv::create(&c, "/test/foo", "bar", None))
+        .run(|c| kv::create(c, "/test/foo", "bar", None))
         .unwrap();
 
     let result = client.run(|c| kv::create_in_order(c, "/test/foo", "baz", None));
@@ -222,6 +227,31 @@ fn get() {
     assert_eq!(node.ttl.unwrap(), 60);
 }
 
+#[test]
+fn get_with_strong_consistency() {
+    let log = Arc::new(RequestLog::default());
+    let builder = ClientBuilder::new(&["http://etcd:2379"]).with_interceptor(log.clone());
+    let client = TestClient::from_builder(builder);
+
+    client.run(|c| async move {
+        kv::set(c, "/test/foo", "bar", None).await.unwrap();
+
+        let options = GetOptions {
+            strong_consistency: true,
+            ..Default::default()
+        };
+        let response = kv::get(c, "/test/foo", options).await.unwrap();
+        assert_eq!(response.data.node.value.unwrap(), "bar");
+        kv::get(c, "/test/foo", GetOptions::default())
+            .await
+            .unwrap();
+    });
+
+    let urls = log.urls();
+    assert!(urls[1].contains("quorum=true"), "{}", urls[1]);
+    assert!(!urls[2].contains("quorum"), "{}", urls[2]);
+}
+
 #[test]
 fn get_non_recursive() {
     let client = TestClient::new();
@@ -247,11 +277,11 @@ fn get_non_recursive() {
         .unwrap();
 
     let node = res.data.node;
-    assert_eq!(node.dir.unwrap(), true);
+    assert!(node.dir.unwrap());
 
     let nodes = node.nodes.unwrap();
     assert_eq!(nodes[0].clone().key.unwrap(), "/test/dir");
-    assert_eq!(nodes[0].clone().dir.unwrap(), true);
+    assert!(nodes[0].clone().dir.unwrap());
     assert_eq!(nodes[1].clone().key.unwrap(), "/test/foo");
     assert_eq!(nodes[1].clone().value.unwrap(), "bar");
 }
@@ -285,6 +315,43 @@ fn get_recursive() {
     );
 }
 
+#[test]
+fn get_hedged() {
+    let (stalled_endpoint, requests) = unresponsive_server();
+    let partition = Arc::new(Partition::default());
+    let builder = ClientBuilder::new(&[&stalled_endpoint, "http://etcd:2379"])
+        .with_request_timeout(Duration::from_secs(5))
+        .with_interceptor(partition.clone());
+    let client = TestClient::from_builder(builder);
+
+    client.run(|c| async move {
+        let etcd = Client::new(&["http://etcd:2379"]);
+        kv::set(&etcd, "/test/foo", "bar", None).await.unwrap();
+
+        // Failing both endpoints once puts them in the order they were given, so that the stalled
+        // endpoint is tried first.
+        partition.set(true);
+        assert!(kv::get(c, "/test/foo", Default::default()).await.is_err());
+        partition.set(false);
+
+        let options = GetOptions {
+            hedge_delay: Some(Duration::from_millis(200)),
+            ..Default::default()
+        };
+        let start = Instant::now();
+        let response = kv::get(c, "/test/foo", options).await.unwrap();
+        let elapsed = start.elapsed();
+        assert_eq!(response.data.node.value.unwrap(), "bar");
+
+        assert!(requests
+            .recv_timeout(Duration::from_secs(1))
+            .unwrap()
+            .starts_with("GET /v2/keys/test/foo"));
+        assert!(elapsed >= Duration::from_millis(200));
+        assert!(elapsed < Duration::from_secs(1));
+    });
+}
+
 #[test]
 fn get_root() {
     let client = TestClient::new();
@@ -302,9 +369,10 @@ fn get_root() {
     assert!(node.created_index.is_none());
     assert!(node.modified_index.is_none());
     assert_eq!(node.nodes.unwrap().len(), 1);
-    assert_eq!(node.dir.unwrap(), true);
+    assert!(node.dir.unwrap());
 }
 
+#[cfg(any(feature = "tls", feature = "rustls-tls"))]
 #[test]
 fn https() {
     let client = TestClient::https(true);
@@ -314,6 +382,7 @@ fn https() {
         .unwrap();
 }
 
+#[cfg(any(feature = "tls", feature = "rustls-tls"))]
 #[test]
 fn https_without_valid_client_certificate() {
     let client = TestClient::https(false);
@@ -361,9 +430,8 @@ fn set_dir() {
     let client = TestClient::new();
 
     client.run(|c| kv::set_dir(c, "/test", None)).unwrap();
-    match client.run(|c| kv::set_dir(c, "/test", None)) {
-        Ok(_) => panic!("set_dir should fail on an existing dir"),
-        Err(_) => {}
+    if client.run(|c| kv::set_dir(c, "/test", None)).is_ok() {
+        panic!("set_dir should fail on an existing dir")
     }
 
     client
@@ -478,7 +546,7 @@ fn delete_dir() {
 fn watch() {
     let client = TestClient::new();
     let create_response = client
-        .run(|c| kv::create(&c, "/test/foo", "bar", None))
+        .run(|c| kv::create(c, "/test/foo", "bar", None))
         .unwrap();
     let set_response = client
         .run(|c| kv::set(c, "/test/foo", "baz", None))
@@ -588,3 +656,144 @@ fn watch_recursive() {
     assert_eq!(node.key.unwrap(), "/test/foo/bar");
     assert_eq!(node.value.unwrap(), "baz");
 }
+
+#[test]
+fn watch_stream() {
+    let client = TestClient::new();
+    let create_response = client
+        .run(|c| kv::create(c, "/test/foo", "bar", None))
+        .unwrap();
+    client
+        .run(|c| kv::set(c, "/test/foo", "baz", None))
+        .unwrap();
+    client
+        .run(|c| kv::set(c, "/test/foo", "qux", None))
+        .unwrap();
+
+    let values: Vec<String> = client.run(|c| async move {
+        kv::watch_stream(
+            c,
+            "/test/foo",
+            WatchOptions {
+                index: Some(create_response.data.node.created_index.unwrap() + 1),
+                ..Default::default()
+            },
+        )
+        .take(2)
+        .map(|result| match result.unwrap() {
+            WatchEvent::Change(kvi) => kvi.node.value.unwrap(),
+            WatchEvent::Resync(_) => panic!("expected WatchEvent::Change"),
+        })
+        .collect()
+        .await
+    });
+
+    assert_eq!(values, vec!["baz".to_owned(), "qux".to_owned()]);
+}
+
+#[test]
+fn watch_stream_resync() {
+    let client = TestClient::new();
+    let create_response = client
+        .run(|c| kv::create(c, "/test/foo/bar", "0", None))
+        .unwrap();
+
+    // Push the creation out of etcd's event history, which holds the last 1000 events.
+    client.run(|c| async move {
+        for i in 1..=1001 {
+            kv::set(c, "/test/foo/bar", &i.to_string(), None)
+                .await
+                .unwrap();
+        }
+    });
+
+    let event = client
+        .run(|c| async move {
+            Box::pin(kv::watch_stream(
+                c,
+                "/test/foo",
+                WatchOptions {
+                    index: Some(create_response.data.node.created_index.unwrap() + 1),
+                    recursive: true,
+                    ..Default::default()
+                },
+            ))
+            .next()
+            .await
+        })
+        .unwrap()
+        .unwrap();
+
+    match event {
+        WatchEvent::Resync(node) => {
+            let nodes = node.nodes.unwrap();
+            assert_eq!(nodes[0].key.as_ref().unwrap(), "/test/foo/bar");
+            assert_eq!(nodes[0].value.as_ref().unwrap(), "1001");
+        }
+        WatchEvent::Change(_) => panic!("expected WatchEvent::Resync"),
+    }
+}
+
+#[test]
+fn watch_stream_backs_off_after_failures() {
+    let builder = ClientBuilder::new(&["http://127.0.0.1:1"]);
+    let client = TestClient::from_builder(builder);
+
+    let elapsed = client.run(|c| async move {
+        let start = Instant::now();
+        let results: Vec<_> = kv::watch_stream(c, "/test/foo", WatchOptions::default())
+            .take(4)
+            .collect()
+            .await;
+        assert!(results.iter().all(Result::is_err));
+        start.elapsed()
+    });
+
+    // The three retries wait for at least half of 100, 200 and 400 milliseconds.
+    assert!(elapsed >= Duration::from_millis(350), "{:?}", elapsed);
+}
+
+#[test]
+fn reflector() {
+    let client = TestClient::new();
+    client
+        .run(|c| kv::set(c, "/test/foo/bar", "baz", None))
+        .unwrap();
+    client
+        .run(|c| kv::set(c, "/test/foo/dir/qux", "quux", None))
+        .unwrap();
+
+    client.run(|c| async move {
+        let reflector = Reflector::new(c, "/test/foo").await.unwrap();
+        let snapshot = reflector.snapshot();
+        let keys: Vec<&str> = snapshot.nodes().keys().map(String::as_str).collect();
+        assert_eq!(
+            keys,
+            vec!["/test/foo/bar", "/test/foo/dir", "/test/foo/dir/qux"]
+        );
+
+        let mut changes = reflector.subscribe();
+        let response = kv::delete(c, "/test/foo/dir", true).await.unwrap();
+        let index = response.data.node.modified_index.unwrap();
+
+        while changes.borrow().index() < index {
+            tokio::time::timeout(Duration::from_secs(5), changes.changed())
+                .await
+                .unwrap()
+                .unwrap();
+        }
+
+        let snapshot = reflector.snapshot();
+        let keys: Vec<&str> = snapshot.nodes().keys().map(String::as_str).collect();
+        assert_eq!(keys, vec!["/test/foo/bar"]);
+        assert_eq!(
+            snapshot
+                .get("/test/foo/bar")
+                .unwrap()
+                .value
+                .as_ref()
+                .unwrap(),
+            "baz"
+        );
+    });
+}
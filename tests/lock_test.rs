@@ -0,0 +1,62 @@
+use std::time::{Duration, Instant};
+
+use etcd::recipes::lock::Mutex;
+use tokio::time::timeout;
+
+use crate::test::{Contender, Partition, TestClient};
+
+mod test;
+
+#[test]
+fn lock() {
+    let client = TestClient::new();
+
+    client.run(|c| async move {
+        let mutex = Mutex::new(c, "/test/lock", 10);
+        let guard = mutex.lock().await.unwrap();
+        assert!(guard.is_held());
+
+        let contender = Mutex::new(c, "/test/lock", 10);
+        let waiting = Contender::spawn(async move { contender.lock().await.unwrap() }).await;
+
+        let first_token = guard.modified_index();
+        guard.unlock().await.unwrap();
+
+        let second = waiting.unblocked().await;
+        assert!(second.modified_index() > first_token);
+    });
+}
+
+#[test]
+fn lock_released_on_drop() {
+    let client = TestClient::new();
+
+    client.run(|c| async move {
+        let mutex = Mutex::new(c, "/test/lock", 10);
+        drop(mutex.lock().await.unwrap());
+
+        timeout(Duration::from_secs(5), mutex.lock())
+            .await
+            .unwrap()
+            .unwrap();
+    });
+}
+
+#[test]
+fn lock_lost_when_refresh_fails() {
+    let client = TestClient::new();
+
+    client.run(|_| async move {
+        let (partitioned, partition) = Partition::client();
+        let mutex = Mutex::new(&partitioned, "/test/lock", 3);
+        let guard = mutex.lock().await.unwrap();
+
+        let start = Instant::now();
+        partition.set(true);
+        assert!(guard.is_held());
+
+        timeout(Duration::from_secs(5), guard.lost()).await.unwrap();
+        assert!(!guard.is_held());
+        assert!(start.elapsed() >= Duration::from_secs(2));
+    });
+}
@@ -7,7 +7,7 @@ mod test;
 #[test]
 fn list() {
     let client = TestClient::no_destructor();
-    let res = client.run(|c| members::list(c)).unwrap();
+    let res = client.run(members::list).unwrap();
     let members = res.data;
     let member = &members[0];
     assert_eq!(member.name, "default");
@@ -0,0 +1,62 @@
+use std::time::Duration;
+
+use etcd::recipes::queue::Queue;
+use tokio::time::timeout;
+
+use crate::test::{Contender, TestClient};
+
+mod test;
+
+#[test]
+fn push_and_pop() {
+    let client = TestClient::new();
+
+    client.run(|c| async move {
+        let queue = Queue::new(c, "/test/queue");
+        assert!(queue.try_pop().await.unwrap().is_none());
+
+        queue.push("a").await.unwrap();
+        queue.push("b").await.unwrap();
+
+        assert_eq!(queue.try_pop().await.unwrap().unwrap().value(), "a");
+        assert_eq!(queue.pop().await.unwrap().value(), "b");
+
+        let consumer = queue.clone();
+        let popping = Contender::spawn(async move { consumer.pop().await.unwrap() }).await;
+
+        queue.push("c").await.unwrap();
+        let item = popping.unblocked().await;
+        assert_eq!(item.value(), "c");
+        assert!(queue.try_pop().await.unwrap().is_none());
+    });
+}
+
+#[test]
+fn visibility_timeout() {
+    let client = TestClient::new();
+
+    client.run(|c| async move {
+        let queue = Queue::with_visibility_timeout(c, "/test/queue", 2);
+        queue.push("a").await.unwrap();
+        queue.push("b").await.unwrap();
+
+        let a = queue.pop().await.unwrap();
+        assert_eq!(a.value(), "a");
+        let b = queue.pop().await.unwrap();
+        assert_eq!(b.value(), "b");
+        assert!(queue.try_pop().await.unwrap().is_none());
+
+        queue.ack(&b).await.unwrap();
+
+        // The unacked item is delivered again once its visibility timeout passes.
+        let redelivered = timeout(Duration::from_secs(5), queue.pop())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(redelivered.key(), a.key());
+        assert!(queue.ack(&a).await.is_err());
+
+        queue.ack(&redelivered).await.unwrap();
+        assert!(queue.try_pop().await.unwrap().is_none());
+    });
+}
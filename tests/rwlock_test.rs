@@ -0,0 +1,50 @@
+use std::time::Duration;
+
+use etcd::recipes::rwlock::RwLock;
+use tokio::time::timeout;
+
+use crate::test::{Contender, TestClient};
+
+mod test;
+
+#[test]
+fn readers_share_lock() {
+    let client = TestClient::new();
+
+    client.run(|c| async move {
+        let lock = RwLock::new(c, "/test/rwlock", 10);
+        let first = lock.read().await.unwrap();
+        let second = timeout(Duration::from_secs(5), lock.read())
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(first.is_held());
+        assert!(!second.is_write());
+
+        let writer = lock.clone();
+        let writing = Contender::spawn(async move { writer.write().await.unwrap() }).await;
+
+        first.unlock().await.unwrap();
+        writing.assert_blocked().await;
+
+        drop(second);
+        let guard = writing.unblocked().await;
+        assert!(guard.is_write());
+    });
+}
+
+#[test]
+fn writer_excludes_readers() {
+    let client = TestClient::new();
+
+    client.run(|c| async move {
+        let lock = RwLock::new(c, "/test/rwlock", 10);
+        let writer = lock.write().await.unwrap();
+
+        let reader = lock.clone();
+        let reading = Contender::spawn(async move { reader.read().await.unwrap() }).await;
+
+        writer.unlock().await.unwrap();
+        reading.unblocked().await;
+    });
+}
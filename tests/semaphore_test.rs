@@ -0,0 +1,66 @@
+use std::time::Duration;
+
+use etcd::recipes::semaphore::Semaphore;
+use tokio::time::timeout;
+
+use crate::test::{Contender, TestClient};
+
+mod test;
+
+#[test]
+fn acquire() {
+    let client = TestClient::new();
+
+    client.run(|c| async move {
+        let semaphore = Semaphore::new(c, "/test/semaphore", 2, 10);
+        let first = semaphore.acquire_permit().await.unwrap();
+        let second = semaphore.acquire_permit().await.unwrap();
+        assert!(first.is_held());
+        assert!(second.is_held());
+
+        let contender = semaphore.clone();
+        let waiting =
+            Contender::spawn(async move { contender.acquire_permit().await.unwrap() }).await;
+
+        first.release().await.unwrap();
+        waiting.unblocked().await;
+    });
+}
+
+#[test]
+fn acquire_without_semaphore() {
+    let client = TestClient::new();
+
+    client.run(|c| async move {
+        let permit = Semaphore::acquire(c, "/test/semaphore", 1, 10)
+            .await
+            .unwrap();
+        assert!(permit.is_held());
+
+        let contender = c.clone();
+        let waiting = Contender::spawn(async move {
+            Semaphore::acquire(&contender, "/test/semaphore", 1, 10)
+                .await
+                .unwrap()
+        })
+        .await;
+
+        permit.release().await.unwrap();
+        waiting.unblocked().await;
+    });
+}
+
+#[test]
+fn permit_released_on_drop() {
+    let client = TestClient::new();
+
+    client.run(|c| async move {
+        let semaphore = Semaphore::new(c, "/test/semaphore", 1, 10);
+        drop(semaphore.acquire_permit().await.unwrap());
+
+        timeout(Duration::from_secs(5), semaphore.acquire_permit())
+            .await
+            .unwrap()
+            .unwrap();
+    });
+}
@@ -7,13 +7,13 @@ mod test;
 #[test]
 fn leader_stats() {
     let client = TestClient::no_destructor();
-    client.run(|c| stats::leader_stats(&c)).unwrap();
+    client.run(stats::leader_stats).unwrap();
 }
 
 #[test]
 fn self_stats() {
     let client = TestClient::no_destructor();
-    let results = client.run(|c| stats::self_stats(&c));
+    let results = client.run(stats::self_stats);
     for result in results {
         result.unwrap();
     }
@@ -22,7 +22,7 @@ fn self_stats() {
 #[test]
 fn store_stats() {
     let client = TestClient::no_destructor();
-    let results = client.run(|c| stats::store_stats(&c));
+    let results = client.run(stats::store_stats);
     for result in results {
         result.unwrap();
     }
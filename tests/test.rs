@@ -1,9 +1,20 @@
+#[cfg(any(feature = "tls", feature = "rustls-tls"))]
+use std::fs::File;
+use std::future::Future;
 use std::io::Read;
-use std::{fs::File, future::Future};
+use std::net::TcpListener;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
 
-use etcd::{kv, Client, ClientBuilder};
+use etcd::{kv, Client, ClientBuilder, InterceptedRequest, Interceptor};
+#[cfg(any(feature = "tls", feature = "rustls-tls"))]
 use reqwest::{Certificate, Identity};
 use tokio::runtime::Runtime;
+use tokio::task::JoinHandle;
+use tokio::time::timeout;
 
 /// Wrapper around Client that automatically cleans up etcd after each test.
 pub struct TestClient {
@@ -14,7 +25,7 @@ pub struct TestClient {
 
 impl TestClient {
     /// Creates a new client for a test.
-    #[allow(dead_code)]
+    #[allow(dead_code, clippy::new_without_default)]
     pub fn new() -> Self {
         Self {
             client: Client::new(&["http://etcd:2379"]),
@@ -23,6 +34,22 @@ impl TestClient {
         }
     }
 
+    /// Creates a new client for a test from a custom builder.
+    #[allow(dead_code)]
+    pub fn from_builder(builder: ClientBuilder) -> Self {
+        let runtime = Runtime::new().expect("failed to create Tokio runtime");
+        let client = {
+            let _guard = runtime.enter();
+            builder.build()
+        };
+
+        Self {
+            client,
+            run_destructor: true,
+            runtime,
+        }
+    }
+
     /// Creates a new client for a test that will not clean up the key space afterwards.
     #[allow(dead_code)]
     pub fn no_destructor() -> Self {
@@ -32,6 +59,7 @@ impl TestClient {
     }
 
     /// Creates a new HTTPS client for a test.
+    #[cfg(any(feature = "tls", feature = "rustls-tls"))]
     #[allow(dead_code)]
     pub fn https(use_client_cert: bool) -> TestClient {
         let client_builder = ClientBuilder::new(&["https://etcdsecure:2379"]);
@@ -43,13 +71,11 @@ impl TestClient {
         let certificate = Certificate::from_der(&ca_cert_buffer).unwrap();
         let client_builder = client_builder.with_root_certificate(certificate);
 
-        let client_builder = if use_client_cert {
-            let mut pkcs12_file = File::open("/source/tests/ssl/client.p12").unwrap();
-            let mut pkcs12_buffer = Vec::new();
-            pkcs12_file.read_to_end(&mut pkcs12_buffer).unwrap();
+        #[cfg(feature = "rustls-tls")]
+        let client_builder = client_builder.with_rustls_tls();
 
-            let identity = Identity::from_pkcs12_der(&pkcs12_buffer, "secret").unwrap();
-            client_builder.with_client_identity(identity)
+        let client_builder = if use_client_cert {
+            client_builder.with_client_identity(client_identity())
         } else {
             client_builder
         };
@@ -62,6 +88,31 @@ impl TestClient {
     }
 }
 
+/// Loads the client certificate used by HTTPS tests, in the format the TLS backend expects.
+#[cfg(feature = "rustls-tls")]
+fn client_identity() -> Identity {
+    let mut pem_buffer = Vec::new();
+    for path in &[
+        "/source/tests/ssl/client.pem",
+        "/source/tests/ssl/client-key.pem",
+    ] {
+        let mut pem_file = File::open(path).unwrap();
+        pem_file.read_to_end(&mut pem_buffer).unwrap();
+    }
+
+    Identity::from_pem(&pem_buffer).unwrap()
+}
+
+/// Loads the client certificate used by HTTPS tests, in the format the TLS backend expects.
+#[cfg(all(feature = "tls", not(feature = "rustls-tls")))]
+fn client_identity() -> Identity {
+    let mut pkcs12_file = File::open("/source/tests/ssl/client.p12").unwrap();
+    let mut pkcs12_buffer = Vec::new();
+    pkcs12_file.read_to_end(&mut pkcs12_buffer).unwrap();
+
+    Identity::from_pkcs12_der(&pkcs12_buffer, "secret").unwrap()
+}
+
 impl TestClient {
     #[allow(dead_code)]
     pub fn run<'a, F, U, R>(&'a self, func: F) -> R
@@ -82,3 +133,121 @@ impl Drop for TestClient {
         }
     }
 }
+
+/// An interceptor that sends requests to an unreachable endpoint while the client is partitioned,
+/// so that the keys the client keeps alive expire as if its process had died.
+#[derive(Debug, Default)]
+#[allow(dead_code)]
+pub struct Partition {
+    partitioned: AtomicBool,
+}
+
+#[allow(dead_code)]
+impl Partition {
+    /// Creates a client for etcd that can be partitioned with the returned interceptor.
+    pub fn client() -> (Client, Arc<Partition>) {
+        let partition = Arc::new(Partition::default());
+        let client = ClientBuilder::new(&["http://etcd:2379"])
+            .with_interceptor(partition.clone())
+            .build();
+        (client, partition)
+    }
+
+    /// Starts or stops sending the client's requests to an unreachable endpoint.
+    pub fn set(&self, partitioned: bool) {
+        self.partitioned.store(partitioned, Ordering::SeqCst);
+    }
+}
+
+impl Interceptor for Partition {
+    fn before_request(&self, request: &mut InterceptedRequest<'_>) {
+        if self.partitioned.load(Ordering::SeqCst) {
+            let mut url = request.url().clone();
+            url.set_host(Some("127.0.0.1")).unwrap();
+            url.set_port(Some(1)).unwrap();
+            request.set_url(url);
+        }
+    }
+}
+
+/// A task contending for a recipe, such as a lock, that is expected to be blocked by another
+/// process.
+#[allow(dead_code)]
+pub struct Contender<T> {
+    task: JoinHandle<T>,
+}
+
+#[allow(dead_code)]
+impl<T: Send + 'static> Contender<T> {
+    /// Spawns a task running `future`, and asserts that it is still blocked 500 milliseconds later.
+    pub async fn spawn<F>(future: F) -> Self
+    where
+        F: Future<Output = T> + Send + 'static,
+    {
+        let contender = Contender {
+            task: tokio::spawn(future),
+        };
+        contender.assert_blocked().await;
+        contender
+    }
+
+    /// Asserts that the task is still blocked 500 milliseconds from now.
+    pub async fn assert_blocked(&self) {
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        assert!(!self.task.is_finished(), "contender was not blocked");
+    }
+
+    /// Waits up to 5 seconds for the task to get through, returning its output.
+    pub async fn unblocked(self) -> T {
+        timeout(Duration::from_secs(5), self.task)
+            .await
+            .expect("contender was not unblocked")
+            .unwrap()
+    }
+}
+
+/// An interceptor that records the URL of every request a client sends.
+#[derive(Debug, Default)]
+#[allow(dead_code)]
+pub struct RequestLog {
+    urls: Mutex<Vec<String>>,
+}
+
+#[allow(dead_code)]
+impl RequestLog {
+    /// Returns the URLs of the requests sent so far, in order.
+    pub fn urls(&self) -> Vec<String> {
+        self.urls.lock().unwrap().clone()
+    }
+}
+
+impl Interceptor for RequestLog {
+    fn before_request(&self, request: &mut InterceptedRequest<'_>) {
+        self.urls.lock().unwrap().push(request.url().to_string());
+    }
+}
+
+/// Starts a server that accepts connections but never responds, passing each request it receives
+/// to the returned channel.
+///
+/// Clients for the server need a request timeout, so that cleaning up after a test doesn't hang.
+#[allow(dead_code)]
+pub fn unresponsive_server() -> (String, Receiver<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let endpoint = format!("http://{}", listener.local_addr().unwrap());
+    let (sender, receiver) = mpsc::channel();
+
+    thread::spawn(move || {
+        let mut connections = Vec::new();
+        for mut stream in listener.incoming().flatten() {
+            let mut buffer = [0; 4096];
+            let length = stream.read(&mut buffer).unwrap_or(0);
+            sender
+                .send(String::from_utf8_lossy(&buffer[..length]).into_owned())
+                .ok();
+            connections.push(stream);
+        }
+    });
+
+    (endpoint, receiver)
+}
@@ -0,0 +1,161 @@
+#![cfg(feature = "tracing")]
+
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+
+use etcd::kv;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::Subscriber;
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+use tracing_subscriber::registry::{LookupSpan, Registry};
+
+use crate::test::TestClient;
+
+mod test;
+
+/// A span created while a `SpanRecorder` was installed, with the fields recorded on it.
+#[derive(Clone, Debug)]
+struct RecordedSpan {
+    id: u64,
+    name: &'static str,
+    parent: Option<&'static str>,
+    fields: HashMap<&'static str, String>,
+}
+
+/// A layer that keeps every span created, along with the fields recorded on it.
+#[derive(Clone, Debug, Default)]
+struct SpanRecorder {
+    spans: Arc<Mutex<Vec<RecordedSpan>>>,
+}
+
+impl SpanRecorder {
+    /// Runs `f` with a recorder installed on the current thread, returning the spans it created.
+    fn record(f: impl FnOnce()) -> Vec<RecordedSpan> {
+        let recorder = SpanRecorder::default();
+        tracing::subscriber::with_default(Registry::default().with(recorder.clone()), f);
+        let spans = recorder.spans.lock().unwrap().clone();
+        spans
+    }
+}
+
+impl<S> Layer<S> for SpanRecorder
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attributes: &Attributes<'_>, id: &Id, context: Context<'_, S>) {
+        let mut fields = HashMap::new();
+        attributes.record(&mut FieldVisitor(&mut fields));
+        let parent = context
+            .span(id)
+            .and_then(|span| span.parent())
+            .map(|parent| parent.name());
+
+        self.spans.lock().unwrap().push(RecordedSpan {
+            id: id.into_u64(),
+            name: attributes.metadata().name(),
+            parent,
+            fields,
+        });
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, _context: Context<'_, S>) {
+        let mut spans = self.spans.lock().unwrap();
+        if let Some(span) = spans.iter_mut().rev().find(|span| span.id == id.into_u64()) {
+            values.record(&mut FieldVisitor(&mut span.fields));
+        }
+    }
+}
+
+/// Collects the fields recorded on a span as strings.
+struct FieldVisitor<'a>(&'a mut HashMap<&'static str, String>);
+
+impl Visit for FieldVisitor<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name(), value.to_owned());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        self.0.insert(field.name(), format!("{:?}", value));
+    }
+}
+
+/// Returns the span with the given name.
+fn span<'a>(spans: &'a [RecordedSpan], name: &str) -> &'a RecordedSpan {
+    spans
+        .iter()
+        .find(|span| span.name == name)
+        .unwrap_or_else(|| panic!("no {} span in {:?}", name, spans))
+}
+
+/// Returns the first endpoint span created within the span with the given name.
+fn attempt<'a>(spans: &'a [RecordedSpan], parent: &str) -> &'a RecordedSpan {
+    spans
+        .iter()
+        .find(|span| span.name == "etcd.attempt" && span.parent == Some(parent))
+        .unwrap_or_else(|| panic!("no etcd.attempt span within {} in {:?}", parent, spans))
+}
+
+#[test]
+fn kv_spans() {
+    let client = TestClient::new();
+
+    let spans = SpanRecorder::record(|| {
+        client.run(|c| async move {
+            kv::set(c, "/test/foo", "bar", None).await.unwrap();
+            assert!(kv::get(c, "/test/missing", Default::default())
+                .await
+                .is_err());
+        })
+    });
+
+    assert_eq!(span(&spans, "etcd.kv.set").fields["key"], "/test/foo");
+    let set = attempt(&spans, "etcd.kv.set");
+    assert_eq!(set.fields["endpoint"], "http://etcd:2379/");
+    assert_eq!(set.fields["http.status"], "201");
+    for field in &[
+        "etcd.cluster_id",
+        "etcd.etcd_index",
+        "etcd.raft_index",
+        "etcd.raft_term",
+    ] {
+        assert!(set.fields.contains_key(field), "{} not recorded", field);
+    }
+    assert!(!set.fields.contains_key("etcd.error_code"));
+
+    assert_eq!(span(&spans, "etcd.kv.get").fields["key"], "/test/missing");
+    let get = attempt(&spans, "etcd.kv.get");
+    assert_eq!(get.fields["http.status"], "404");
+    assert_eq!(get.fields["etcd.error_code"], "100");
+}
+
+#[test]
+fn client_spans() {
+    let client = TestClient::no_destructor();
+
+    let spans = SpanRecorder::record(|| {
+        client.run(|c| async move {
+            for result in c.health().await {
+                result.unwrap();
+            }
+            for result in c.versions().await {
+                result.unwrap();
+            }
+            c.sync_endpoints().await.unwrap();
+        })
+    });
+
+    assert_eq!(
+        attempt(&spans, "etcd.client.health").fields["http.status"],
+        "200"
+    );
+    assert_eq!(
+        attempt(&spans, "etcd.client.versions").fields["http.status"],
+        "200"
+    );
+    assert_eq!(
+        span(&spans, "etcd.members.list").parent,
+        Some("etcd.client.sync_endpoints")
+    );
+}
@@ -0,0 +1,127 @@
+#![cfg(unix)]
+
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+use std::os::unix::net::UnixListener;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::Duration;
+use std::{env, fs, process};
+
+use etcd::kv::{self, WatchOptions};
+use etcd::{members, stats, ClientBuilder, RetryPolicy};
+
+use crate::test::TestClient;
+
+mod test;
+
+/// A server listening on a Unix domain socket that relays every connection to etcd, passing the
+/// first request it receives on each connection to its channel.
+struct SocketServer {
+    path: PathBuf,
+    requests: Receiver<String>,
+}
+
+impl SocketServer {
+    fn start(name: &str) -> Self {
+        let path = env::temp_dir().join(format!("etcd-{}-{}.sock", name, process::id()));
+        fs::remove_file(&path).ok();
+        let listener = UnixListener::bind(&path).unwrap();
+        let (sender, requests) = mpsc::channel();
+
+        thread::spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                let mut head = Vec::new();
+                let mut byte = [0; 1];
+                while !head.ends_with(b"\r\n\r\n") && stream.read(&mut byte).unwrap_or(0) == 1 {
+                    head.push(byte[0]);
+                }
+                sender
+                    .send(String::from_utf8_lossy(&head).into_owned())
+                    .ok();
+
+                let mut upstream = TcpStream::connect("etcd:2379").unwrap();
+                upstream.write_all(&head).unwrap();
+                let mut downstream = stream.try_clone().unwrap();
+                let mut upstream_reader = upstream.try_clone().unwrap();
+                thread::spawn(move || io::copy(&mut stream, &mut upstream));
+                thread::spawn(move || io::copy(&mut upstream_reader, &mut downstream));
+            }
+        });
+
+        SocketServer { path, requests }
+    }
+
+    fn endpoint(&self) -> String {
+        format!("unix://{}", self.path.display())
+    }
+}
+
+impl Drop for SocketServer {
+    fn drop(&mut self) {
+        fs::remove_file(&self.path).ok();
+    }
+}
+
+#[test]
+fn unix_socket_endpoint() {
+    let server = SocketServer::start("endpoint");
+    let builder = ClientBuilder::new(&[&server.endpoint()]);
+    let client = TestClient::from_builder(builder);
+
+    client.run(|c| async move {
+        assert_eq!(c.endpoints()[0].scheme_str(), Some("unix"));
+
+        kv::set(c, "/test/foo", "bar", None).await.unwrap();
+        let res = kv::get(c, "/test/foo", Default::default()).await.unwrap();
+        assert_eq!(res.data.node.value.unwrap(), "bar");
+
+        let options = WatchOptions {
+            index: res.data.node.modified_index,
+            ..Default::default()
+        };
+        let res = kv::watch(c, "/test/foo", options).await.unwrap();
+        assert_eq!(res.data.node.value.unwrap(), "bar");
+
+        assert!(!members::list(c).await.unwrap().data.is_empty());
+        for result in stats::self_stats(c).await {
+            result.unwrap();
+        }
+        for result in c.health().await {
+            assert_eq!(result.unwrap().data.health, "true");
+        }
+    });
+
+    let request = server.requests.recv().unwrap();
+    assert!(request.starts_with("PUT /v2/keys/test/foo HTTP/1.1\r\n"));
+}
+
+#[test]
+fn unix_socket_endpoint_failover() {
+    let path = env::temp_dir().join(format!("etcd-missing-{}.sock", process::id()));
+    let endpoint = format!("unix://{}", path.display());
+    let builder = ClientBuilder::new(&[&endpoint, "http://etcd:2379"]);
+    let client = TestClient::from_builder(builder);
+
+    client
+        .run(|c| kv::set(c, "/test/foo", "bar", None))
+        .unwrap();
+}
+
+#[test]
+fn unix_socket_errors_are_retried() {
+    let path = env::temp_dir().join(format!("etcd-missing-{}.sock", process::id()));
+    let endpoint = format!("unix://{}", path.display());
+    let retry_policy =
+        RetryPolicy::new(3).with_backoff(Duration::from_millis(10), Duration::from_millis(10));
+    let builder = ClientBuilder::new(&[&endpoint])
+        .with_endpoint_ejection(u32::MAX, Duration::from_secs(60))
+        .with_retry_policy(retry_policy);
+    let client = TestClient::from_builder(builder);
+
+    client.run(|c| async move {
+        assert!(kv::get(c, "/test/foo", Default::default()).await.is_err());
+        assert_eq!(c.endpoint_health()[0].consecutive_failures, 3);
+    });
+}